@@ -0,0 +1,28 @@
+/// Panics if the AUTH_SECRET is not set or is invalid.
+/// used at boot to make sure the app is setup
+pub fn verify_auth_key() {
+    let _ = auth_key();
+}
+
+/// Panics if the AUTH_SECRET is not set or is invalid.
+/// used at boot to make sure the app is setup
+pub(crate) fn auth_key() -> Vec<u8> {
+    use base64::prelude::*;
+    let key_base64 =
+        std::env::var("AUTH_SECRET").expect("\n\nAUTH_SECRET env not set. expected a AES_256_KEY\nYou can generate an AUTH_SECRET for your gumbo project to use by running the command:\ngumbo generate env\n\n");
+    let key_bytes = BASE64_STANDARD
+        .decode(key_base64)
+        .expect("\nFailed to read env AUTH_SECRET. expected a AES_256_KEY\nYou can generate an AUTH_SECRET for your gumbo project to use by running the command:\ngumbo generate env\n\n");
+    assert_eq!(key_bytes.len(), 32, "Key must be 256 bits (32 bytes)");
+    key_bytes
+}
+
+/// Sets the same AUTH_SECRET for every test, the environment is shared by the whole process
+#[cfg(test)]
+pub(crate) fn set_test_auth_secret() {
+    use base64::prelude::*;
+    static SET: std::sync::Once = std::sync::Once::new();
+    SET.call_once(|| {
+        std::env::set_var("AUTH_SECRET", BASE64_STANDARD.encode([7u8; 32]));
+    });
+}
@@ -0,0 +1,45 @@
+/// Stable DOM ids for a model, so views and Turbo Streams agree on what to target.
+///
+/// ```
+/// use gumbo_lib::dom_id::DomId;
+///
+/// struct Dog {
+///     id: Option<i64>,
+/// }
+///
+/// impl DomId for Dog {
+///     fn dom_prefix() -> &'static str {
+///         "dog"
+///     }
+///
+///     fn dom_key(&self) -> Option<String> {
+///         self.id.map(|id| id.to_string())
+///     }
+/// }
+///
+/// assert_eq!(Dog { id: Some(12) }.dom_id(), "dog_12");
+/// assert_eq!(Dog { id: None }.dom_id(), "new_dog");
+/// assert_eq!(Dog { id: Some(12) }.dom_id_with("edit"), "edit_dog_12");
+/// ```
+pub trait DomId {
+    /// The singular name of the model, for example "dog"
+    fn dom_prefix() -> &'static str;
+
+    /// What makes this record unique, usually its primary key.
+    /// None for a record that hasn't been saved yet
+    fn dom_key(&self) -> Option<String>;
+
+    /// `dog_12`, or `new_dog` for a record without a key
+    fn dom_id(&self) -> String {
+        let prefix = Self::dom_prefix();
+        match self.dom_key() {
+            Some(key) => format!("{prefix}_{key}"),
+            None => format!("new_{prefix}"),
+        }
+    }
+
+    /// The dom_id with another prefix added to it, for example `edit_dog_12`
+    fn dom_id_with(&self, prefix: &str) -> String {
+        format!("{prefix}_{}", self.dom_id())
+    }
+}
@@ -0,0 +1,150 @@
+use crate::view::escape_attr;
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use regex::Regex;
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+use std::rc::Rc;
+use yew::html::BaseComponent;
+use yew::virtual_dom::{VComp, VNode};
+use yew::{html, Children, Component, Context, ContextProvider, Html, Properties};
+
+#[derive(Properties, PartialEq)]
+pub struct TurboFrameProps {
+    pub id: String,
+    /// Load the frame's content from this URL
+    #[prop_or_default]
+    pub src: Option<String>,
+    /// Set to "lazy" to wait to load the src until the frame is visible
+    #[prop_or_default]
+    pub loading: Option<String>,
+    /// Set to "_top" to have links inside the frame navigate the whole page
+    #[prop_or_default]
+    pub target: Option<String>,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A `<turbo-frame>` element.
+///
+/// Render the view holding it with `view::render_frame`,
+/// so requests from this frame only render the frame.
+#[yew::function_component(TurboFrame)]
+pub fn turbo_frame(props: &TurboFrameProps) -> Html {
+    let src = props.src.clone().map(crate::view::app_path);
+    html! {
+        <turbo-frame
+            id={ props.id.clone() }
+            src={ src }
+            loading={ props.loading.clone() }
+            target={ props.target.clone() }>
+            { props.children.clone() }
+        </turbo-frame>
+    }
+}
+
+/// The `Turbo-Frame` header Turbo sends when a request is made from inside a `<turbo-frame>`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurboFrameRequest(Option<String>);
+
+impl TurboFrameRequest {
+    /// The id of the frame that made this request
+    pub fn id(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    pub fn is_frame_request(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// Allows you to request the TurboFrameRequest from an actix resource
+impl FromRequest for TurboFrameRequest {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let id = req
+            .headers()
+            .get("Turbo-Frame")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+        ready(Ok(TurboFrameRequest(id)))
+    }
+}
+
+/// The id of the frame the current request is rendering for, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RequestedFrame(Option<String>);
+
+/// Returns the id of the `<turbo-frame>` this request is being rendered for.
+///
+/// Layouts can use this to skip rendering heavy parts of the page
+/// that are thrown away when only a single frame is sent to the browser.
+#[yew::hook]
+pub fn use_turbo_frame() -> Option<String> {
+    yew::use_context::<RequestedFrame>().and_then(|f| f.0)
+}
+
+/// Wraps a page to provide the RequestedFrame to every view inside of it
+pub(crate) struct FrameRoot<V: BaseComponent> {
+    _marker: PhantomData<V>,
+}
+
+#[derive(Properties)]
+pub(crate) struct FrameRootProps<V: BaseComponent> {
+    pub frame: Option<String>,
+    pub props: Rc<V::Properties>,
+}
+
+impl<V: BaseComponent> PartialEq for FrameRootProps<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.frame == other.frame && self.props == other.props
+    }
+}
+
+impl<V: BaseComponent> Component for FrameRoot<V> {
+    type Message = ();
+    type Properties = FrameRootProps<V>;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let page = VNode::from(VComp::new::<V>(props.props.clone(), None));
+        html! {
+            <ContextProvider<RequestedFrame> context={ RequestedFrame(props.frame.clone()) }>
+                { page }
+            </ContextProvider<RequestedFrame>>
+        }
+    }
+}
+
+/// Finds the `<turbo-frame>` with the given id in the rendered html,
+/// including any frames nested inside of it.
+pub(crate) fn extract_frame<'a>(html: &'a str, id: &str) -> Option<&'a str> {
+    // Yew escapes attributes when rendering, so the id has to be escaped to match
+    let id = escape_attr(id);
+    // `\s` rather than `\b`, which would also match `data-id`
+    let pattern = format!(r#"<turbo-frame\b[^>]*\sid="{}"[^>]*>"#, regex::escape(&id));
+    let open = Regex::new(&pattern).ok()?.find(html)?;
+    let tags = Regex::new(r"<turbo-frame\b|</turbo-frame\s*>").unwrap();
+
+    let mut depth = 1;
+    for tag in tags.find_iter(&html[open.end()..]) {
+        if tag.as_str().starts_with("</") {
+            depth -= 1;
+        } else {
+            depth += 1;
+        }
+        if depth == 0 {
+            let end = open.end() + tag.end();
+            return Some(&html[open.start()..end]);
+        }
+    }
+    None
+}
@@ -0,0 +1,233 @@
+use crate::view::Render;
+use actix_web::HttpResponse;
+use regex::Regex;
+use std::collections::HashMap;
+use std::rc::Rc;
+use yew::html::BaseComponent;
+use yew::{
+    function_component, html, use_context, AttrValue, Children, ContextProvider, Html, Properties,
+    ServerRenderer,
+};
+
+/// The element ContentFor wraps its children in, until they are moved into the layout
+const SLOT_TAG: &str = "gumbo-content-for";
+
+#[derive(Properties, PartialEq)]
+pub struct ContentForProps {
+    /// The name of the slot in the layout, for example "title" or "head"
+    pub slot: AttrValue,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// Set while rendering the children of a ContentFor
+#[derive(Debug, Clone, PartialEq)]
+struct InContentFor;
+
+/// Moves its children out of the page and into a named slot of the layout.
+/// Can be used anywhere in the page, as many times as needed.
+///
+/// A ContentFor can't be nested inside another one. A nested ContentFor is ignored,
+/// its children stay in the slot of the outer one.
+///
+/// Only works when the page is rendered with `layout::render`
+#[function_component(ContentFor)]
+pub fn content_for(props: &ContentForProps) -> Html {
+    if use_context::<InContentFor>().is_some() {
+        log::warn!("ContentFor::nested slot {} is ignored", props.slot);
+        return html! { <>{ props.children.clone() }</> };
+    }
+    html! {
+        <ContextProvider<InContentFor> context={ InContentFor }>
+            <@{SLOT_TAG} slot={ props.slot.clone() }>{ props.children.clone() }</@>
+        </ContextProvider<InContentFor>>
+    }
+}
+
+/// What a layout is given to render: the page, what the page put in each slot,
+/// and the data the handler gave the layout, for example the Session
+#[derive(Properties, Debug)]
+pub struct LayoutProps<LP: 'static = ()> {
+    /// The rendered page
+    pub body: AttrValue,
+    /// The content for each slot, in the order it appeared in the page
+    pub slots: Rc<HashMap<String, String>>,
+    /// The layout args given to `render`
+    pub data: Rc<LP>,
+}
+
+impl<LP> Clone for LayoutProps<LP> {
+    fn clone(&self) -> Self {
+        Self {
+            body: self.body.clone(),
+            slots: self.slots.clone(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<LP> PartialEq for LayoutProps<LP> {
+    fn eq(&self, other: &Self) -> bool {
+        self.body == other.body && self.slots == other.slots && Rc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl<LP> LayoutProps<LP> {
+    /// The rendered page
+    pub fn body(&self) -> Html {
+        Html::from_html_unchecked(self.body.clone())
+    }
+
+    /// Everything the page put in the slot. Empty if nothing was
+    pub fn slot(&self, name: &str) -> Html {
+        match self.slots.get(name) {
+            Some(content) => Html::from_html_unchecked(AttrValue::from(content.clone())),
+            None => Html::default(),
+        }
+    }
+
+    pub fn has_slot(&self, name: &str) -> bool {
+        self.slots.contains_key(name)
+    }
+}
+
+/// Render a Yew view inside a layout to send out in an Actix Response.
+///
+/// The layout is rendered after the page, so it can use everything the page
+/// put in its slots with `ContentFor`. The layout args are given to the layout as `data`.
+///
+/// ```
+/// use gumbo_lib::layout::{ContentFor, LayoutProps};
+/// use yew::{function_component, html, Html};
+///
+/// pub struct LayoutData {
+///     pub user_name: Option<String>,
+/// }
+///
+/// #[function_component(AppLayout)]
+/// pub fn app_layout(props: &LayoutProps<LayoutData>) -> Html {
+///     html! {
+///         <html>
+///             <head>
+///                 <title>{ props.slot("title") }</title>
+///                 { props.slot("head") }
+///             </head>
+///             <body>
+///                 <nav>{ props.data.user_name.clone().unwrap_or_default() }</nav>
+///                 { props.body() }
+///             </body>
+///         </html>
+///     }
+/// }
+///
+/// #[function_component(DogsIndex)]
+/// pub fn dogs_index() -> Html {
+///     html! {
+///         <>
+///             <ContentFor slot="title">{ "Dogs" }</ContentFor>
+///             <h1>{ "Dogs" }</h1>
+///         </>
+///     }
+/// }
+///
+/// // in an actix endpoint
+/// async fn index() -> actix_web::Result<actix_web::HttpResponse> {
+///     let data = LayoutData { user_name: None };
+///     gumbo_lib::layout::render::<AppLayout, DogsIndex, _, _, _>(data, ()).await
+/// }
+/// ```
+pub async fn render<L, V, LP, VM, E>(layout_args: LP, args: VM) -> Result<HttpResponse, E>
+where
+    L: BaseComponent<Properties = LayoutProps<LP>>,
+    V: BaseComponent,
+    V: BaseComponent<Properties = VM>,
+    LP: Send + 'static,
+    VM: Send + 'static,
+{
+    let html = render_to_string::<L, V, LP, VM>(layout_args, args).await;
+    Ok(Render::page().html(html))
+}
+
+/// Render a page inside a layout, returning the HTML without a doctype
+pub async fn render_to_string<L, V, LP, VM>(layout_args: LP, args: VM) -> String
+where
+    L: BaseComponent<Properties = LayoutProps<LP>>,
+    V: BaseComponent,
+    V: BaseComponent<Properties = VM>,
+    LP: Send + 'static,
+    VM: Send + 'static,
+{
+    let page = ServerRenderer::<V>::with_props(|| args)
+        .hydratable(false)
+        .render()
+        .await;
+    let (body, slots) = extract_slots(&page);
+    // LayoutProps isn't Send, so it is built inside the renderer
+    ServerRenderer::<L>::with_props(move || LayoutProps {
+        body: AttrValue::from(body),
+        slots: Rc::new(slots),
+        data: Rc::new(layout_args),
+    })
+    .hydratable(false)
+    .render()
+    .await
+}
+
+/// Pulls the ContentFor elements out of the page, grouping their contents by slot.
+/// ContentFor never renders one inside another, so the first closing tag is always its own
+fn extract_slots(page: &str) -> (String, HashMap<String, String>) {
+    let pattern = format!(r#"(?s)<{SLOT_TAG} slot="([^"]*)">(.*?)</{SLOT_TAG}>"#);
+    let re = Regex::new(&pattern).unwrap();
+    let mut slots: HashMap<String, String> = HashMap::default();
+    for caps in re.captures_iter(page) {
+        slots
+            .entry(caps[1].to_owned())
+            .or_default()
+            .push_str(&caps[2]);
+    }
+    let body = re.replace_all(page, "").into_owned();
+    (body, slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct LayoutData {
+        user_name: &'static str,
+    }
+
+    #[function_component(AppLayout)]
+    fn app_layout(props: &LayoutProps<LayoutData>) -> Html {
+        html! {
+            <body>
+                <title>{ props.slot("title") }</title>
+                <nav>{ props.data.user_name }</nav>
+                <main>{ props.body() }</main>
+            </body>
+        }
+    }
+
+    #[function_component(DogsIndex)]
+    fn dogs_index() -> Html {
+        html! {
+            <>
+                <ContentFor slot="title">
+                    { "Dogs" }
+                    <ContentFor slot="head">{ "!" }</ContentFor>
+                </ContentFor>
+                <h1>{ "Dogs" }</h1>
+            </>
+        }
+    }
+
+    #[actix_web::test]
+    async fn layouts_get_slots_and_data() {
+        let data = LayoutData { user_name: "bob" };
+        let html = render_to_string::<AppLayout, DogsIndex, _, _>(data, ()).await;
+        assert_eq!(
+            html,
+            "<body><title>Dogs!</title><nav>bob</nav><main><h1>Dogs</h1></main></body>"
+        );
+    }
+}
@@ -1,13 +1,26 @@
+pub mod dom_id;
 pub mod errors;
+pub mod frame;
 pub mod javascript;
+pub mod layout;
+pub mod respond;
 pub mod view;
 
+#[cfg(any(feature = "sessions", feature = "turbo-streams"))]
+mod auth_secret;
+
+#[cfg(any(feature = "sessions", feature = "turbo-streams"))]
+pub use auth_secret::verify_auth_key;
+
 #[cfg(feature = "sessions")]
 pub mod session;
 
 #[cfg(feature = "sessions")]
 pub use session::Session;
 
+#[cfg(feature = "sessions")]
+pub mod policy;
+
 #[cfg(feature = "turbo-streams")]
 pub mod turbo;
 
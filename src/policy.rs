@@ -0,0 +1,252 @@
+use crate::view::Render;
+use crate::Session;
+use actix_web::error::{ErrorForbidden, InternalError};
+use actix_web::http::StatusCode;
+use actix_web::{FromRequest, HttpRequest};
+use std::rc::Rc;
+use yew::html::BaseComponent;
+use yew::{html, Children, ContextProvider, Html, Properties};
+
+/// The actions a Policy can allow or deny on a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Show,
+    Create,
+    Update,
+    Destroy,
+}
+
+/// Decides what a Subject (usually a Session or a User) is allowed to do with a Resource.
+///
+/// Every action is denied unless the policy says otherwise.
+///
+/// ```
+/// use gumbo_lib::policy::Policy;
+/// use gumbo_lib::Session;
+///
+/// struct Dog { owner: String }
+/// struct DogPolicy;
+///
+/// impl Policy<Session, Dog> for DogPolicy {
+///     fn show(&self, _session: &Session, _dog: &Dog) -> bool {
+///         true
+///     }
+///     fn update(&self, session: &Session, dog: &Dog) -> bool {
+///         session.sub() == dog.owner
+///     }
+/// }
+/// ```
+pub trait Policy<Subject, Resource> {
+    fn show(&self, _subject: &Subject, _resource: &Resource) -> bool {
+        false
+    }
+
+    fn create(&self, _subject: &Subject, _resource: &Resource) -> bool {
+        false
+    }
+
+    fn update(&self, _subject: &Subject, _resource: &Resource) -> bool {
+        false
+    }
+
+    fn destroy(&self, _subject: &Subject, _resource: &Resource) -> bool {
+        false
+    }
+
+    /// Returns true if the subject is allowed to perform the action on the resource
+    fn allows(&self, subject: &Subject, action: Action, resource: &Resource) -> bool {
+        match action {
+            Action::Show => self.show(subject, resource),
+            Action::Create => self.create(subject, resource),
+            Action::Update => self.update(subject, resource),
+            Action::Destroy => self.destroy(subject, resource),
+        }
+    }
+}
+
+/// Loads the Session for this request and checks it against a Policy.
+/// Returns the Session if the action is allowed, otherwise a 403 Forbidden.
+///
+/// ```
+/// use actix_web::{HttpRequest, HttpResponse};
+/// use gumbo_lib::policy::{authorize, Action, Policy};
+/// use gumbo_lib::Session;
+///
+/// struct Dog;
+/// struct DogPolicy;
+/// impl Policy<Session, Dog> for DogPolicy {}
+///
+/// async fn edit(req: HttpRequest) -> actix_web::Result<HttpResponse> {
+///     let dog = Dog;
+///     let _session = authorize(&req, &DogPolicy, Action::Update, &dog).await?;
+///     Ok(HttpResponse::Ok().finish())
+/// }
+/// ```
+pub async fn authorize<P, R>(
+    req: &HttpRequest,
+    policy: &P,
+    action: Action,
+    resource: &R,
+) -> Result<Session, actix_web::Error>
+where
+    P: Policy<Session, R>,
+{
+    let session = Session::extract(req).await?;
+    if !policy.allows(&session, action, resource) {
+        log::debug!("authorize::denied {:?}", action);
+        return Err(ErrorForbidden(""));
+    }
+    Ok(session)
+}
+
+/// Loads the Session for this request and checks it against a Policy.
+/// Returns the Session if the action is allowed,
+/// otherwise the Yew view V is rendered as a 403 Forbidden page.
+pub async fn authorize_or_render<V, VM, P, R>(
+    req: &HttpRequest,
+    policy: &P,
+    action: Action,
+    resource: &R,
+    forbidden: VM,
+) -> Result<Session, actix_web::Error>
+where
+    V: BaseComponent,
+    V: BaseComponent<Properties = VM>,
+    VM: Send + 'static,
+    P: Policy<Session, R>,
+{
+    let session = Session::extract(req).await?;
+    if !policy.allows(&session, action, resource) {
+        log::debug!("authorize_or_render::denied {:?}", action);
+        let res = Render::page()
+            .status(StatusCode::FORBIDDEN)
+            .view::<V, VM, actix_web::Error>(forbidden)
+            .await?;
+        return Err(InternalError::from_response("", res).into());
+    }
+    Ok(session)
+}
+
+/// A Policy and the Subject it is checking, shared with Yew views through a context.
+///
+/// Use the `PolicyProvider` component to add one to your view,
+/// and `use_policy` to read it back out deeper in the tree.
+pub struct PolicyContext<P, S> {
+    policy: Rc<P>,
+    subject: Rc<S>,
+}
+
+impl<P, S> PolicyContext<P, S> {
+    /// The subject can be given as an `Rc`, so a Session already shared by the view can be reused
+    pub fn new(policy: P, subject: impl Into<Rc<S>>) -> Self {
+        Self {
+            policy: Rc::new(policy),
+            subject: subject.into(),
+        }
+    }
+
+    /// Returns true if the policy allows the subject to perform the action on the resource
+    pub fn can<R>(&self, action: Action, resource: &R) -> bool
+    where
+        P: Policy<S, R>,
+    {
+        self.policy.allows(&self.subject, action, resource)
+    }
+
+    pub fn subject(&self) -> &S {
+        &self.subject
+    }
+}
+
+impl<P, S> Clone for PolicyContext<P, S> {
+    fn clone(&self) -> Self {
+        Self {
+            policy: self.policy.clone(),
+            subject: self.subject.clone(),
+        }
+    }
+}
+
+impl<P, S> PartialEq for PolicyContext<P, S> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.policy, &other.policy) && Rc::ptr_eq(&self.subject, &other.subject)
+    }
+}
+
+#[derive(Properties)]
+pub struct PolicyProviderProps<P: 'static, S: 'static> {
+    pub context: PolicyContext<P, S>,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+impl<P, S> PartialEq for PolicyProviderProps<P, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.context == other.context && self.children == other.children
+    }
+}
+
+/// Makes a Policy available to every view nested inside of it.
+///
+/// ```
+/// use gumbo_lib::policy::{use_policy, Action, Policy, PolicyContext, PolicyProvider};
+/// use gumbo_lib::Session;
+/// use std::rc::Rc;
+/// use yew::prelude::*;
+///
+/// struct Dog;
+/// struct DogPolicy;
+/// impl Policy<Session, Dog> for DogPolicy {}
+///
+/// #[function_component(EditButton)]
+/// fn edit_button() -> Html {
+///     let policy = use_policy::<DogPolicy, Session>();
+///     let allowed = policy.map(|p| p.can(Action::Update, &Dog)).unwrap_or_default();
+///     if !allowed {
+///         return html! {};
+///     }
+///     html! { <a href="/dogs/1/edit">{"Edit"}</a> }
+/// }
+///
+/// #[derive(Properties, PartialEq)]
+/// struct PageProps { session: Rc<Session> }
+///
+/// #[function_component(Page)]
+/// fn page(props: &PageProps) -> Html {
+///     let context = PolicyContext::new(DogPolicy, props.session.clone());
+///     html! {
+///         <PolicyProvider<DogPolicy, Session> {context}>
+///             <EditButton />
+///         </PolicyProvider<DogPolicy, Session>>
+///     }
+/// }
+/// ```
+pub struct PolicyProvider<P: 'static, S: 'static> {
+    _marker: std::marker::PhantomData<(P, S)>,
+}
+
+impl<P: 'static, S: 'static> yew::Component for PolicyProvider<P, S> {
+    type Message = ();
+    type Properties = PolicyProviderProps<P, S>;
+
+    fn create(_ctx: &yew::Context<Self>) -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    fn view(&self, ctx: &yew::Context<Self>) -> Html {
+        let props = ctx.props();
+        html! {
+            <ContextProvider<PolicyContext<P, S>> context={props.context.clone()}>
+                { props.children.clone() }
+            </ContextProvider<PolicyContext<P, S>>>
+        }
+    }
+}
+
+/// Returns the PolicyContext provided by a parent `PolicyProvider`, if there is one
+#[yew::hook]
+pub fn use_policy<P: 'static, S: 'static>() -> Option<PolicyContext<P, S>> {
+    yew::use_context::<PolicyContext<P, S>>()
+}
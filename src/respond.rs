@@ -0,0 +1,140 @@
+use actix_web::http::header::{HeaderValue, ACCEPT, VARY};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+
+/// The content type of Turbo Stream messages.
+/// Turbo also sends it in the Accept header when it can take a Turbo Stream back
+pub const TURBO_STREAM_MIME: &str = "text/vnd.turbo-stream.html";
+
+type ResponseFuture<E> = Pin<Box<dyn Future<Output = Result<HttpResponse, E>>>>;
+type Branch<E> = Box<dyn FnOnce() -> ResponseFuture<E>>;
+
+/// True when the client said it would take a Turbo Stream as the response
+pub fn accepts_turbo_stream(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|range| range.split(';').next())
+        .any(|mime| mime.trim().eq_ignore_ascii_case(TURBO_STREAM_MIME))
+}
+
+/// Picks how to respond based on the Accept header of the request.
+///
+/// Turbo asks for `text/vnd.turbo-stream.html` on form submissions.
+/// Those get the turbo_stream branch, everyone else gets the html branch.
+/// Only the chosen branch is rendered, and `Vary: Accept` is always set.
+///
+/// ```
+/// use actix_web::{HttpRequest, HttpResponse};
+/// use gumbo_lib::respond::respond_to;
+/// use gumbo_lib::view::{redirect, render_turbo_stream};
+/// # #[yew::function_component(DogCreated)]
+/// # fn dog_created() -> yew::Html { yew::html! {} }
+///
+/// async fn create(req: HttpRequest) -> actix_web::Result<HttpResponse> {
+///     respond_to(&req)
+///         .turbo_stream(|| render_turbo_stream::<DogCreated, _, _>(()))
+///         .html(|| async { redirect("/dogs") })
+///         .await
+/// }
+/// ```
+///
+/// When a form is not valid, re-render it with `invalid()`.
+/// Turbo ignores html form responses unless they are a redirect or a 422
+///
+/// ```
+/// use actix_web::{HttpRequest, HttpResponse};
+/// use gumbo_lib::respond::respond_to;
+/// use gumbo_lib::view::render;
+/// # #[yew::function_component(NewDog)]
+/// # fn new_dog() -> yew::Html { yew::html! {} }
+///
+/// async fn create(req: HttpRequest) -> actix_web::Result<HttpResponse> {
+///     respond_to(&req)
+///         .html(|| render::<NewDog, _, _>(()))
+///         .invalid()
+///         .await
+/// }
+/// ```
+pub fn respond_to<E: 'static>(req: &HttpRequest) -> RespondTo<E> {
+    RespondTo {
+        wants_turbo_stream: accepts_turbo_stream(req),
+        turbo_stream: None,
+        html: None,
+        status: None,
+    }
+}
+
+/// Built with `respond_to`. Await it to render the chosen branch
+pub struct RespondTo<E> {
+    wants_turbo_stream: bool,
+    turbo_stream: Option<Branch<E>>,
+    html: Option<Branch<E>>,
+    status: Option<StatusCode>,
+}
+
+impl<E: 'static> RespondTo<E> {
+    /// The response sent when the client accepts Turbo Streams
+    pub fn turbo_stream<F, Fut>(mut self, branch: F) -> Self
+    where
+        F: FnOnce() -> Fut + 'static,
+        Fut: Future<Output = Result<HttpResponse, E>> + 'static,
+    {
+        self.turbo_stream = Some(Box::new(move || Box::pin(branch())));
+        self
+    }
+
+    /// The response sent to everyone else.
+    /// Also used for Turbo when there is no turbo_stream branch
+    pub fn html<F, Fut>(mut self, branch: F) -> Self
+    where
+        F: FnOnce() -> Fut + 'static,
+        Fut: Future<Output = Result<HttpResponse, E>> + 'static,
+    {
+        self.html = Some(Box::new(move || Box::pin(branch())));
+        self
+    }
+
+    /// Respond with a 422 Unprocessable Entity. Used when a form is not valid
+    pub fn invalid(self) -> Self {
+        self.status(StatusCode::UNPROCESSABLE_ENTITY)
+    }
+
+    /// Override the status code of whichever branch is chosen.
+    /// Only successful (2xx) responses are changed, so redirects and errors are kept
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Render the branch that matches the Accept header.
+    /// Responds with 406 Not Acceptable if there is no branch to render
+    pub async fn respond(self) -> Result<HttpResponse, E> {
+        let branch = match self.wants_turbo_stream {
+            true => self.turbo_stream.or(self.html),
+            false => self.html,
+        };
+        let mut res = match branch {
+            Some(branch) => branch().await?,
+            None => HttpResponse::NotAcceptable().finish(),
+        };
+        if let Some(status) = self.status.filter(|_| res.status().is_success()) {
+            *res.status_mut() = status;
+        }
+        res.headers_mut()
+            .append(VARY, HeaderValue::from_static("Accept"));
+        Ok(res)
+    }
+}
+
+impl<E: 'static> IntoFuture for RespondTo<E> {
+    type Output = Result<HttpResponse, E>;
+    type IntoFuture = ResponseFuture<E>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.respond())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::auth_secret::auth_key;
 use actix_web::error::ErrorUnauthorized;
 use actix_web::http::Method;
 use actix_web::FromRequest;
@@ -10,8 +11,8 @@ use rand::distr::Alphanumeric;
 use rand::Rng;
 use rkyv::{deserialize, Archive, Deserialize, Serialize};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use yew::html;
 use yew::virtual_dom::vnode::VNode;
+use yew::{function_component, html, Html, Properties};
 
 /// An Active Users Session
 /// If you want to store info about this user You should go make a user table/model
@@ -32,8 +33,23 @@ pub struct Session {
     exp: i64,
     // The expected csrf_token for this given session
     csrf_token: String,
+    // The sub of the admin that is impersonating this user, if any
+    actor: Option<String>,
 }
 
+/// Sessions from before impersonation was added, read so upgrading doesn't log everyone out.
+/// Their bytes start with the text of a String, which is never 0xFF
+#[derive(Archive, Deserialize, Serialize)]
+struct SessionV1 {
+    sub: String,
+    exp: i64,
+    csrf_token: String,
+}
+
+/// Written in front of the Session bytes, so the layout can change again later.
+/// Sessions written with it can't be read by versions of this crate from before it was added
+const SESSION_FORMAT: [u8; 2] = [0xFF, 2];
+
 /// An Active Users Session that does NOT verify a csrf-token
 pub struct SessionUnsafe(Session);
 impl SessionUnsafe {
@@ -43,21 +59,64 @@ impl SessionUnsafe {
 }
 
 impl Session {
+    /// The effective identity of this session.
+    /// When impersonating this is the user being impersonated.
     pub fn sub(&self) -> &str {
         &self.sub
     }
 
+    /// The real person behind this session. Use this when writing audit logs.
+    /// When impersonating this is the admin, otherwise it is the same as `sub()`
+    pub fn actor(&self) -> &str {
+        self.actor.as_deref().unwrap_or(&self.sub)
+    }
+
+    /// Returns true if an admin is using this session to impersonate another user
+    pub fn is_impersonating(&self) -> bool {
+        self.actor.is_some()
+    }
+
+    /// Returns true once this session is past its expiration time
+    pub fn is_expired(&self) -> bool {
+        self.exp < now_sec()
+    }
+
+    /// How long until this session expires. Zero if it already has
+    pub fn expires_in(&self) -> Duration {
+        let remaining = self.exp - now_sec();
+        Duration::from_secs(remaining.max(0) as u64)
+    }
+
     /// This is called when a user is logged in
     pub fn build(sub: impl Into<String>) -> Session {
-        let csrf_token: String = rand::rng()
-            .sample_iter(&Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect();
         Session {
             sub: sub.into(),
-            csrf_token,
+            csrf_token: new_csrf_token(),
             exp: next_exp_time(),
+            actor: None,
+        }
+    }
+
+    /// Returns a new session that lets the actor of this session act as the given sub.
+    /// Checking that the actor is allowed to impersonate is up to your app.
+    /// The new session expires at the same time as this one.
+    pub fn impersonate(&self, sub: impl Into<String>) -> Session {
+        Session {
+            sub: sub.into(),
+            csrf_token: new_csrf_token(),
+            exp: self.exp,
+            actor: Some(self.actor().to_owned()),
+        }
+    }
+
+    /// Returns a session for the real actor, ending any impersonation.
+    /// The new session expires at the same time as this one.
+    pub fn stop_impersonating(&self) -> Session {
+        Session {
+            sub: self.actor().to_owned(),
+            csrf_token: new_csrf_token(),
+            exp: self.exp,
+            actor: None,
         }
     }
 
@@ -74,8 +133,13 @@ impl Session {
         let serialized =
             rkyv::to_bytes::<rkyv::rancor::Error>(self).expect("Session Serialization Failed");
 
+        let plaintext: Vec<u8> = SESSION_FORMAT
+            .iter()
+            .chain(serialized.iter())
+            .cloned()
+            .collect();
         let cipherbytes: Vec<u8> = cipher
-            .encrypt(&nonce, serialized.as_ref())
+            .encrypt(&nonce, plaintext.as_ref())
             .expect("Serialization failed");
 
         let allbytes: Vec<u8> = noncebytes
@@ -101,7 +165,17 @@ impl Session {
             .decrypt(nonce, contents)
             .or(Err(ErrorUnauthorized("")))?;
 
-        let archived = rkyv::access::<ArchivedSession, rkyv::rancor::Error>(&bytes)
+        match bytes.strip_prefix(&SESSION_FORMAT) {
+            Some(serialized) => Session::from_serialized(serialized),
+            None => Session::from_v1(&bytes),
+        }
+    }
+
+    fn from_serialized(serialized: &[u8]) -> Result<Session, actix_web::Error> {
+        // rkyv needs the bytes aligned, which they aren't after the format prefix
+        let mut aligned = rkyv::util::AlignedVec::<16>::new();
+        aligned.extend_from_slice(serialized);
+        let archived = rkyv::access::<ArchivedSession, rkyv::rancor::Error>(&aligned)
             .or(Err(ErrorUnauthorized("")))?;
 
         let session =
@@ -110,31 +184,71 @@ impl Session {
         Ok(session)
     }
 
+    fn from_v1(serialized: &[u8]) -> Result<Session, actix_web::Error> {
+        let archived = rkyv::access::<ArchivedSessionV1, rkyv::rancor::Error>(serialized)
+            .or(Err(ErrorUnauthorized("")))?;
+        let old = deserialize::<SessionV1, rkyv::rancor::Error>(archived)
+            .or(Err(ErrorUnauthorized("")))?;
+        Ok(Session {
+            sub: old.sub,
+            exp: old.exp,
+            csrf_token: old.csrf_token,
+            actor: None,
+        })
+    }
+
     /// Add this to the top of your html page.
     pub fn meta_csrf_token(&self) -> VNode {
         html! {
             <meta name="csrf-token" content={ self.csrf_token.clone() } />
         }
     }
+
+    /// Add this to the top of your html body.
+    /// Shows an ImpersonationBanner when this session is impersonating someone.
+    /// stop_path should be the route that calls `stop_impersonating`
+    pub fn impersonation_banner(&self, stop_path: impl Into<String>) -> VNode {
+        match &self.actor {
+            Some(actor) => html! {
+                <ImpersonationBanner
+                    actor={ actor.clone() }
+                    sub={ self.sub.clone() }
+                    stop_path={ stop_path.into() } />
+            },
+            None => html! {},
+        }
+    }
 }
 
-/// Panics if the AUTH_SECRET is not set or is invalid.
-/// used at boot to make sure the app is setup
-pub fn verify_auth_key() {
-    let _ = auth_key();
+#[derive(Properties, PartialEq)]
+pub struct ImpersonationBannerProps {
+    pub actor: String,
+    pub sub: String,
+    pub stop_path: String,
 }
 
-/// Panics if the AUTH_SECRET is not set or is invalid.
-/// used at boot to make sure the app is setup
-fn auth_key() -> Vec<u8> {
-    use base64::prelude::*;
-    let key_base64 =
-        std::env::var("AUTH_SECRET").expect("\n\nAUTH_SECRET env not set. expected a AES_256_KEY\nYou can generate an AUTH_SECRET for your gumbo project to use by running the command:\ngumbo generate env\n\n");
-    let key_bytes = BASE64_STANDARD
-        .decode(key_base64)
-        .expect("\nFailed to read env AUTH_SECRET. expected a AES_256_KEY\nYou can generate an AUTH_SECRET for your gumbo project to use by running the command:\ngumbo generate env\n\n");
-    assert_eq!(key_bytes.len(), 32, "Key must be 256 bits (32 bytes)");
-    key_bytes
+/// A banner that lets an admin know they are impersonating someone
+/// and gives them a link to go back to being themselves.
+#[function_component(ImpersonationBanner)]
+pub fn impersonation_banner(props: &ImpersonationBannerProps) -> Html {
+    let stop_path = crate::view::app_path(props.stop_path.clone());
+    html! {
+        <div class="impersonation-banner" role="alert">
+            <span>{ format!("{} is impersonating {}", props.actor, props.sub) }</span>
+            <a href={ stop_path } data-turbo-method="delete">{ "Stop impersonating" }</a>
+        </div>
+    }
+}
+
+pub use crate::auth_secret::verify_auth_key;
+
+/// a new random token to verify forms against
+fn new_csrf_token() -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
 }
 
 /// returns the time now
@@ -226,3 +340,82 @@ async fn load_session_unsafe(
     // NOTE: not verifying the csrf_token
     Ok(SessionUnsafe(session))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_secret::set_test_auth_secret;
+
+    fn decode(encrypted: &str) -> Session {
+        let bytes = BASE64_STANDARD.decode(encrypted).unwrap();
+        Session::from_encrypted(&bytes).unwrap()
+    }
+
+    /// Encrypts a session the way it was saved before impersonation was added
+    fn encrypt_v1(old: &SessionV1) -> Vec<u8> {
+        let key_bytes = auth_key();
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let serialized = rkyv::to_bytes::<rkyv::rancor::Error>(old).unwrap();
+        let cipherbytes = cipher.encrypt(&nonce, serialized.as_ref()).unwrap();
+        nonce.iter().chain(cipherbytes.iter()).cloned().collect()
+    }
+
+    #[test]
+    fn v1_sessions_still_decode() {
+        set_test_auth_secret();
+        for sub in [
+            "",
+            "bob",
+            "a much longer subject than fits inline",
+            "ünïcödé",
+        ] {
+            let old = SessionV1 {
+                sub: sub.to_owned(),
+                exp: 1234,
+                csrf_token: new_csrf_token(),
+            };
+            let session = Session::from_encrypted(&encrypt_v1(&old)).unwrap();
+            assert_eq!(session.sub(), sub);
+            assert_eq!(session.exp, 1234);
+            assert_eq!(session.csrf_token, old.csrf_token);
+            assert!(!session.is_impersonating());
+        }
+    }
+
+    #[test]
+    fn sessions_round_trip() {
+        set_test_auth_secret();
+        for sub in ["", "bob", "a much longer subject than fits inline"] {
+            let session = Session::build(sub);
+            assert_eq!(decode(&session.as_encrypted()), session);
+        }
+    }
+
+    #[test]
+    fn impersonation_round_trips() {
+        set_test_auth_secret();
+        let admin = Session::build("admin");
+        let impersonating = decode(&admin.impersonate("bob").as_encrypted());
+        assert_eq!(impersonating.sub(), "bob");
+        assert_eq!(impersonating.actor(), "admin");
+        assert!(impersonating.is_impersonating());
+
+        let stopped = decode(&impersonating.stop_impersonating().as_encrypted());
+        assert_eq!(stopped.sub(), "admin");
+        assert_eq!(stopped.actor(), "admin");
+        assert!(!stopped.is_impersonating());
+    }
+
+    #[test]
+    fn tampered_sessions_are_rejected() {
+        set_test_auth_secret();
+        let mut bytes = BASE64_STANDARD
+            .decode(Session::build("bob").as_encrypted())
+            .unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(Session::from_encrypted(&bytes).is_err());
+        assert!(Session::from_encrypted(&bytes[..12]).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::respond::TURBO_STREAM_MIME;
+use crate::view::escape_attr;
+use actix_web::body::BoxBody;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use std::fmt::{Display, Formatter};
+use yew::html::BaseComponent;
+use yew::ServerRenderer;
+
+/// The actions a `<turbo-stream>` element can perform on the page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TurboAction {
+    Append,
+    Prepend,
+    Replace,
+    Update,
+    Remove,
+    Before,
+    After,
+    Refresh,
+}
+
+impl TurboAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TurboAction::Append => "append",
+            TurboAction::Prepend => "prepend",
+            TurboAction::Replace => "replace",
+            TurboAction::Update => "update",
+            TurboAction::Remove => "remove",
+            TurboAction::Before => "before",
+            TurboAction::After => "after",
+            TurboAction::Refresh => "refresh",
+        }
+    }
+}
+
+impl Display for TurboAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What element(s) on the page a TurboStreamAction is applied to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TurboTarget {
+    /// A single element by its DOM id. Rendered as `target=`
+    Id(String),
+    /// Every element matching a CSS selector. Rendered as `targets=`
+    Selector(String),
+}
+
+/// A single typed `<turbo-stream>` message.
+///
+/// Wraps raw HTML or a rendered Yew view in the correct turbo-stream markup
+/// so it can be returned from an actix endpoint or broadcast over a TurboStream.
+///
+/// ```
+/// use gumbo_lib::turbo::TurboStreamAction;
+///
+/// let action = TurboStreamAction::append("dogs").html("<li>Rex</li>");
+/// assert_eq!(
+///     action.to_html(),
+///     r#"<turbo-stream action="append" target="dogs"><template><li>Rex</li></template></turbo-stream>"#
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurboStreamAction {
+    action: TurboAction,
+    target: Option<TurboTarget>,
+    morph: bool,
+    request_id: Option<String>,
+    content: String,
+}
+
+impl TurboStreamAction {
+    pub fn new(action: TurboAction) -> Self {
+        Self {
+            action,
+            target: None,
+            morph: false,
+            request_id: None,
+            content: String::default(),
+        }
+    }
+
+    pub fn append(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::Append).target(target)
+    }
+
+    pub fn prepend(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::Prepend).target(target)
+    }
+
+    pub fn replace(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::Replace).target(target)
+    }
+
+    pub fn update(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::Update).target(target)
+    }
+
+    pub fn remove(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::Remove).target(target)
+    }
+
+    pub fn before(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::Before).target(target)
+    }
+
+    pub fn after(target: impl Into<String>) -> Self {
+        Self::new(TurboAction::After).target(target)
+    }
+
+    /// Asks the page to reload itself (Turbo 8)
+    pub fn refresh() -> Self {
+        Self::new(TurboAction::Refresh)
+    }
+
+    /// Apply this action to the element with the given DOM id
+    pub fn target(mut self, id: impl Into<String>) -> Self {
+        self.target = Some(TurboTarget::Id(id.into()));
+        self
+    }
+
+    /// Apply this action to every element matching the CSS selector
+    pub fn targets(mut self, selector: impl Into<String>) -> Self {
+        self.target = Some(TurboTarget::Selector(selector.into()));
+        self
+    }
+
+    /// Use Turbo 8 morphing (`method="morph"`) instead of replacing the elements
+    pub fn morph(mut self) -> Self {
+        self.morph = true;
+        self
+    }
+
+    /// Tag the action with the `X-Turbo-Request-Id` that caused it.
+    /// Turbo ignores a `refresh` carrying the id of a request it just made
+    pub fn request_id(mut self, id: impl Into<String>) -> Self {
+        self.request_id = Some(id.into());
+        self
+    }
+
+    /// Set the raw HTML that will be placed inside the `<template>`
+    pub fn html(mut self, html: impl Into<String>) -> Self {
+        self.content = html.into();
+        self
+    }
+
+    /// Render a Yew view as the contents of the `<template>`
+    pub async fn render<V, VM>(mut self, args: VM) -> Self
+    where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        let renderer = ServerRenderer::<V>::with_props(|| args).hydratable(false);
+        self.content = renderer.render().await;
+        self
+    }
+
+    pub fn action(&self) -> TurboAction {
+        self.action
+    }
+
+    pub fn turbo_target(&self) -> Option<&TurboTarget> {
+        self.target.as_ref()
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// The full `<turbo-stream>` markup for this action
+    pub fn to_html(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for TurboStreamAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, r#"<turbo-stream action="{}""#, self.action)?;
+        if self.morph {
+            f.write_str(r#" method="morph""#)?;
+        }
+        match &self.target {
+            Some(TurboTarget::Id(id)) => write!(f, r#" target="{}""#, escape_attr(id))?,
+            Some(TurboTarget::Selector(s)) => write!(f, r#" targets="{}""#, escape_attr(s))?,
+            None => (),
+        }
+        if let Some(id) = &self.request_id {
+            write!(f, r#" request-id="{}""#, escape_attr(id))?;
+        }
+        f.write_str(">")?;
+        // remove and refresh don't carry any content
+        if !matches!(self.action, TurboAction::Remove | TurboAction::Refresh) {
+            write!(f, "<template>{}</template>", self.content)?;
+        }
+        f.write_str("</turbo-stream>")
+    }
+}
+
+/// Allows a TurboStreamAction to be returned directly from an actix endpoint
+impl Responder for TurboStreamAction {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .content_type(TURBO_STREAM_MIME)
+            .body(self.to_html())
+    }
+}
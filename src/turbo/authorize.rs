@@ -0,0 +1,130 @@
+use super::{
+    ChannelPattern, LastEventId, SignedStreamName, TurboConnectionId, TurboSse, TurboStream,
+};
+use crate::session::SessionUnsafe;
+use crate::Session;
+use actix_web::error::ErrorForbidden;
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How often an open stream checks that it is still authorized
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Decides if a visitor may watch a channel.
+///
+/// The session is None when the visitor is not logged in.
+///
+/// ```
+/// use gumbo_lib::turbo::ChannelAuthorizer;
+/// use gumbo_lib::Session;
+///
+/// struct ProjectChannels;
+///
+/// impl ChannelAuthorizer for ProjectChannels {
+///     fn authorize(&self, session: Option<&Session>, channel: &str) -> bool {
+///         match session {
+///             Some(session) => channel == format!("users/{}", session.sub()),
+///             None => channel == "announcements",
+///         }
+///     }
+/// }
+/// ```
+pub trait ChannelAuthorizer: Send + Sync + 'static {
+    fn authorize(&self, session: Option<&Session>, channel: &str) -> bool;
+
+    /// How often an open stream is re-authorized.
+    /// Zero re-authorizes before every message, see `TurboSse::guard`
+    fn recheck_interval(&self) -> Duration {
+        RECHECK_INTERVAL
+    }
+}
+
+impl<F> ChannelAuthorizer for F
+where
+    F: Fn(Option<&Session>, &str) -> bool + Send + Sync + 'static,
+{
+    fn authorize(&self, session: Option<&Session>, channel: &str) -> bool {
+        self(session, channel)
+    }
+}
+
+/// An actix endpoint that streams a signed channel over SSE,
+/// but only after the ChannelAuthorizer in the app_data says the visitor may watch it.
+///
+/// While the stream is open the authorizer is consulted again periodically,
+/// and the stream is closed when the session expires.
+///
+/// ```
+/// use actix_web::{web, App};
+/// use gumbo_lib::turbo::{authorized_sse, ChannelAuthorizer, TurboStream};
+/// use gumbo_lib::Session;
+///
+/// struct Everyone;
+/// impl ChannelAuthorizer for Everyone {
+///     fn authorize(&self, _session: Option<&Session>, _channel: &str) -> bool {
+///         true
+///     }
+/// }
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(TurboStream::default()))
+///     .app_data(web::Data::new(Everyone))
+///     .route(
+///         "/streams/{signed_stream_name}",
+///         web::get().to(authorized_sse::<Everyone>),
+///     );
+/// ```
+pub async fn authorized_sse<A: ChannelAuthorizer>(
+    req: HttpRequest,
+    turbo: Data<TurboStream>,
+    authorizer: Data<A>,
+    name: SignedStreamName,
+    last_event_id: LastEventId,
+    connection: TurboConnectionId,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session = load_optional_session(&req).await;
+    let authorizer = authorizer.into_inner();
+    let sse = authorize_sse(authorizer, session, &turbo, name.channel())?;
+    let sse = sse
+        .for_connection(&connection)
+        .resume(last_event_id.as_deref());
+    Ok(sse.into_response())
+}
+
+/// Checks the authorizer and builds a TurboSse for the channel
+/// that closes itself once the visitor is no longer authorized
+pub fn authorize_sse<A: ChannelAuthorizer>(
+    authorizer: Arc<A>,
+    session: Option<Session>,
+    turbo: &TurboStream,
+    channel: &str,
+) -> Result<TurboSse, actix_web::Error> {
+    if !authorizer.authorize(session.as_ref(), channel) {
+        log::debug!("authorize_sse::denied {channel}");
+        return Err(ErrorForbidden(""));
+    }
+    let close_at = session.as_ref().map(|s| Instant::now() + s.expires_in());
+    let every = authorizer.recheck_interval();
+    let owned_channel = channel.to_owned();
+    let guard = move || {
+        if session.as_ref().is_some_and(|s| s.is_expired()) {
+            return false;
+        }
+        authorizer.authorize(session.as_ref(), &owned_channel)
+    };
+    let mut sse = TurboSse::new(turbo.watch(ChannelPattern::exact(channel))).guard(every, guard);
+    if let Some(close_at) = close_at {
+        sse = sse.close_at(close_at);
+    }
+    Ok(sse)
+}
+
+/// The visitors session if they have one.
+/// SSE connections are GETs, so there is no csrf-token to check
+async fn load_optional_session(req: &HttpRequest) -> Option<Session> {
+    let session = SessionUnsafe::extract(req).await.ok()?;
+    Some(session.into_inner())
+}
@@ -0,0 +1,188 @@
+use super::replay::ReplayLog;
+use super::TurboMessage;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::broadcast::Sender;
+
+/// A message on its way to every process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TurboPayload {
+    /// Whoever assigned the id, picked at random when they started.
+    /// 0 until the payload is given an id
+    pub epoch: u64,
+    /// The message id used to replay it to reconnecting clients.
+    ///
+    /// 0 until it is given one. Ids must be assigned in a single place, like the
+    /// `TurboBroker`, so every process agrees on them. When a payload reaches the fanout
+    /// without one, the next id of this process is used.
+    pub id: u64,
+    pub channel: String,
+    pub html: String,
+    /// The connection that caused this message, it is not sent back to it.
+    /// See `TurboStream::stream_except`
+    pub origin: Option<String>,
+}
+
+impl TurboPayload {
+    /// A length prefixed binary encoding, used to send payloads between processes
+    pub fn encode(&self) -> Vec<u8> {
+        // an empty origin is read back as None
+        let origin = self.origin.as_deref().unwrap_or_default();
+        let len = 28 + self.channel.len() + self.html.len() + origin.len();
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&self.epoch.to_be_bytes());
+        out.extend_from_slice(&self.id.to_be_bytes());
+        for field in [self.channel.as_str(), self.html.as_str(), origin] {
+            out.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            out.extend_from_slice(field.as_bytes());
+        }
+        out
+    }
+
+    /// Reads a payload written with `encode`. None if the bytes are not a valid payload
+    pub fn decode(bytes: &[u8]) -> Option<TurboPayload> {
+        let (epoch, rest) = bytes.split_first_chunk::<8>()?;
+        let (id, rest) = rest.split_first_chunk::<8>()?;
+        let (channel, rest) = read_field(rest)?;
+        let (html, rest) = read_field(rest)?;
+        let (origin, rest) = read_field(rest)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        Some(TurboPayload {
+            epoch: u64::from_be_bytes(*epoch),
+            id: u64::from_be_bytes(*id),
+            channel,
+            html,
+            origin: Some(origin).filter(|o| !o.is_empty()),
+        })
+    }
+}
+
+fn read_field(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let (len, rest) = bytes.split_first_chunk::<4>()?;
+    let len = u32::from_be_bytes(*len) as usize;
+    if rest.len() < len {
+        return None;
+    }
+    let (field, rest) = rest.split_at(len);
+    Some((String::from_utf8(field.to_vec()).ok()?, rest))
+}
+
+/// Hands messages to the subscribers in this process.
+///
+/// A TurboBackend receives one of these when it is started,
+/// and delivers every message it receives from other processes to it.
+#[derive(Debug, Clone)]
+pub struct TurboFanout {
+    pub(crate) tx: Sender<Arc<TurboMessage>>,
+    pub(crate) replay: Arc<Mutex<ReplayLog>>,
+}
+
+impl TurboFanout {
+    /// Send the payload to everyone in this process watching its channel.
+    /// Returns the message as it was sent, with its id.
+    ///
+    /// Payloads from other processes should already have an id,
+    /// or clients that reconnect to a different process will be replayed the wrong messages
+    pub fn deliver(&self, payload: TurboPayload) -> Arc<TurboMessage> {
+        // hold the lock while sending so subscribers see ids in order
+        let mut replay = self.replay.lock().unwrap();
+        let msg = replay.record(payload);
+        match self.tx.send(msg.clone()) {
+            Ok(_) => (),
+            Err(err) => log::warn!("TurboStream Error: {:?}", err),
+        }
+        msg
+    }
+}
+
+/// Carries messages from the TurboStream that sent them
+/// to the subscribers watching for them, wherever they are.
+///
+/// The default `LocalBackend` only reaches subscribers in this process.
+/// Implement this trait to fan messages out across processes or machines.
+pub trait TurboBackend: Debug + Send + Sync + 'static {
+    /// Called once when the backend is given to a TurboStream.
+    /// Every message meant for this process should be handed to the fanout
+    fn start(&self, fanout: TurboFanout);
+
+    /// Send the payload out to every process, including this one.
+    ///
+    /// A backend reaching many processes should give the payload an id first,
+    /// the same one in every process
+    fn publish(&self, payload: TurboPayload);
+}
+
+/// The default TurboBackend. Messages only reach subscribers in this process
+#[derive(Debug, Default)]
+pub struct LocalBackend {
+    fanout: OnceLock<TurboFanout>,
+}
+
+impl TurboBackend for LocalBackend {
+    fn start(&self, fanout: TurboFanout) {
+        let _ = self.fanout.set(fanout);
+    }
+
+    fn publish(&self, payload: TurboPayload) {
+        match self.fanout.get() {
+            Some(fanout) => {
+                fanout.deliver(payload);
+            }
+            None => log::warn!("TurboStream Error: LocalBackend was never started"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> TurboPayload {
+        TurboPayload {
+            epoch: 0xfeed,
+            id: 42,
+            channel: "dogs/12".to_owned(),
+            html: "<turbo-stream action=\"refresh\">ünïcödé</turbo-stream>".to_owned(),
+            origin: Some("c0ffee".to_owned()),
+        }
+    }
+
+    #[test]
+    fn payloads_round_trip() {
+        let payload = payload();
+        assert_eq!(TurboPayload::decode(&payload.encode()), Some(payload));
+        let empty = TurboPayload {
+            epoch: 0,
+            id: 0,
+            channel: String::new(),
+            html: String::new(),
+            origin: None,
+        };
+        assert_eq!(TurboPayload::decode(&empty.encode()), Some(empty));
+    }
+
+    #[test]
+    fn truncated_payloads_are_rejected() {
+        let bytes = payload().encode();
+        for len in 0..bytes.len() {
+            assert_eq!(TurboPayload::decode(&bytes[..len]), None, "length {len}");
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut bytes = payload().encode();
+        bytes.push(0);
+        assert_eq!(TurboPayload::decode(&bytes), None);
+    }
+
+    #[test]
+    fn invalid_utf8_is_rejected() {
+        let mut bytes = payload().encode();
+        let last = bytes.len() - 1;
+        bytes[last] = 0xff;
+        assert_eq!(TurboPayload::decode(&bytes), None);
+    }
+}
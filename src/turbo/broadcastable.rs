@@ -0,0 +1,104 @@
+use super::{TurboStream, TurboStreamAction};
+use crate::dom_id::DomId;
+use std::future::Future;
+use yew::html::BaseComponent;
+
+/// A model that sends itself out over a TurboStream as it changes.
+///
+/// Implement it once, naming the channel and the partial that renders the model,
+/// and `broadcast_created`, `broadcast_updated` and `broadcast_destroyed`
+/// send the matching append, replace and remove actions.
+///
+/// The partial should render its root element with the model's `dom_id`
+/// so later updates can find it.
+///
+/// ```
+/// use gumbo_lib::dom_id::DomId;
+/// use gumbo_lib::turbo::{Broadcastable, TurboStream};
+/// use yew::{function_component, html, Html, Properties};
+///
+/// #[derive(Properties, Clone, PartialEq)]
+/// pub struct Dog {
+///     id: i64,
+///     name: String,
+/// }
+///
+/// impl DomId for Dog {
+///     fn dom_prefix() -> &'static str {
+///         "dog"
+///     }
+///     fn dom_key(&self) -> Option<String> {
+///         Some(self.id.to_string())
+///     }
+/// }
+///
+/// #[function_component(DogRow)]
+/// pub fn dog_row(dog: &Dog) -> Html {
+///     html! { <li id={ dog.dom_id() }>{ &dog.name }</li> }
+/// }
+///
+/// impl Broadcastable for Dog {
+///     type Partial = DogRow;
+///
+///     fn broadcast_channel(&self) -> String {
+///         "dogs".to_owned()
+///     }
+///
+///     fn partial_props(&self) -> Dog {
+///         self.clone()
+///     }
+/// }
+///
+/// async fn created(turbo: &TurboStream, dog: &Dog) {
+///     // appends <li id="dog_12"> to the element with the id "dogs"
+///     dog.broadcast_created(turbo).await;
+/// }
+/// ```
+pub trait Broadcastable: DomId {
+    /// The view that renders this model
+    type Partial: BaseComponent<Properties: Send + 'static>;
+
+    /// The channel changes to this model are sent on
+    fn broadcast_channel(&self) -> String;
+
+    /// The properties the Partial is rendered with
+    fn partial_props(&self) -> <Self::Partial as BaseComponent>::Properties;
+
+    /// The DOM id of the list new records are appended to.
+    /// Defaults to the channel name
+    fn broadcast_target(&self) -> String {
+        self.broadcast_channel()
+    }
+
+    /// Append the newly created model to the `broadcast_target`
+    fn broadcast_created(&self, turbo: &TurboStream) -> impl Future<Output = ()> {
+        let action = TurboStreamAction::append(self.broadcast_target());
+        self.broadcast_rendered(turbo, action)
+    }
+
+    /// Replace the model's element with a fresh render of it
+    fn broadcast_updated(&self, turbo: &TurboStream) -> impl Future<Output = ()> {
+        let action = TurboStreamAction::replace(self.dom_id());
+        self.broadcast_rendered(turbo, action)
+    }
+
+    /// Remove the model's element from the page
+    fn broadcast_destroyed(&self, turbo: &TurboStream) {
+        let action = TurboStreamAction::remove(self.dom_id());
+        turbo.broadcast(self.broadcast_channel(), &action);
+    }
+
+    /// Render the Partial into the action and send it on the channel
+    fn broadcast_rendered(
+        &self,
+        turbo: &TurboStream,
+        action: TurboStreamAction,
+    ) -> impl Future<Output = ()> {
+        let channel = self.broadcast_channel();
+        let props = self.partial_props();
+        async move {
+            let action = action.render::<Self::Partial, _>(props).await;
+            turbo.broadcast(channel, &action);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use super::TurboMessage;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// A channel name, or a glob pattern matching many channel names.
+///
+/// - `*` matches anything within a single path segment
+/// - `**` matches anything, including `/`
+///
+/// ```
+/// use gumbo_lib::turbo::ChannelPattern;
+///
+/// let dogs = ChannelPattern::new("dogs/*");
+/// assert!(dogs.matches("dogs/12"));
+/// assert!(!dogs.matches("dogs/12/walks"));
+/// assert!(ChannelPattern::new("dogs/**").matches("dogs/12/walks"));
+/// assert!(ChannelPattern::new("alerts/global").matches("alerts/global"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelPattern {
+    pattern: String,
+    /// Wildcards are matched literally
+    literal: bool,
+}
+
+impl ChannelPattern {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            literal: false,
+        }
+    }
+
+    /// A single channel, where `*` is just another character.
+    ///
+    /// Use this for channel names that come from visitors, so they can't watch
+    /// other channels by sending a glob.
+    ///
+    /// ```
+    /// use gumbo_lib::turbo::ChannelPattern;
+    ///
+    /// let pattern = ChannelPattern::exact("dogs/*");
+    /// assert!(pattern.matches("dogs/*"));
+    /// assert!(!pattern.matches("dogs/12"));
+    /// ```
+    pub fn exact(channel: impl Into<String>) -> Self {
+        Self {
+            pattern: channel.into(),
+            literal: true,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns true if this pattern only matches a single channel
+    pub fn is_exact(&self) -> bool {
+        self.literal || !self.pattern.contains('*')
+    }
+
+    /// Returns true if the channel is matched by this pattern
+    pub fn matches(&self, channel: &str) -> bool {
+        if self.is_exact() {
+            return self.pattern == channel;
+        }
+        glob_match(self.pattern.as_bytes(), channel.as_bytes())
+    }
+}
+
+impl Display for ChannelPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl From<&str> for ChannelPattern {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&String> for ChannelPattern {
+    fn from(value: &String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for ChannelPattern {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+/// A TurboMessage along with the subscribed pattern that matched it
+#[derive(Debug, Clone)]
+pub struct ChannelMatch {
+    pub pattern: ChannelPattern,
+    pub message: Arc<TurboMessage>,
+}
+
+impl ChannelMatch {
+    /// The channel the message was sent on
+    pub fn channel(&self) -> &str {
+        &self.message.channel
+    }
+}
+
+/// Matches in O(pattern * text), so hostile patterns can't stall a broadcast.
+///
+/// Walks the pattern one token at a time, tracking every position in the text
+/// the pattern so far could have stopped at.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let mut reached = vec![false; text.len() + 1];
+    reached[0] = true;
+    let mut p = 0;
+    while p < pattern.len() {
+        let mut next = vec![false; text.len() + 1];
+        match pattern[p..] {
+            [b'*', b'*', ..] => {
+                let mut any = false;
+                for (i, reached) in reached.iter().enumerate() {
+                    any |= *reached;
+                    next[i] = any;
+                }
+                p += 2;
+            }
+            [b'*', ..] => {
+                // a single star can't cross into the next segment
+                next[0] = reached[0];
+                for i in 1..=text.len() {
+                    next[i] = reached[i] || (next[i - 1] && text[i - 1] != b'/');
+                }
+                p += 1;
+            }
+            [c, ..] => {
+                for i in 0..text.len() {
+                    next[i + 1] = reached[i] && text[i] == c;
+                }
+                p += 1;
+            }
+            [] => unreachable!(),
+        }
+        if !next.contains(&true) {
+            return false;
+        }
+        reached = next;
+    }
+    reached[text.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelPattern;
+
+    fn matches(pattern: &str, channel: &str) -> bool {
+        ChannelPattern::new(pattern).matches(channel)
+    }
+
+    #[test]
+    fn single_star_stays_in_its_segment() {
+        assert!(matches("dogs/*", "dogs/12"));
+        assert!(matches("dogs/*", "dogs/"));
+        assert!(!matches("dogs/*", "dogs/12/walks"));
+        assert!(matches("dogs/*/walks", "dogs/12/walks"));
+        assert!(!matches("dogs/*/walks", "dogs/12/13/walks"));
+        assert!(matches("*/12", "dogs/12"));
+        assert!(!matches("*", "dogs/12"));
+        assert!(matches("dogs/1*", "dogs/12"));
+        assert!(matches("dogs/*2", "dogs/12"));
+        assert!(!matches("dogs/*3", "dogs/12"));
+    }
+
+    #[test]
+    fn double_star_crosses_segments() {
+        assert!(matches("dogs/**", "dogs/12"));
+        assert!(matches("dogs/**", "dogs/12/walks"));
+        assert!(matches("dogs/**", "dogs/"));
+        assert!(!matches("dogs/**", "cats/12"));
+        assert!(matches("**", "dogs/12/walks"));
+        assert!(matches("**/walks", "dogs/12/walks"));
+        assert!(matches("dogs/**/walks", "dogs/12/13/walks"));
+        assert!(!matches("dogs/**/walks", "dogs/12/13/runs"));
+    }
+
+    #[test]
+    fn stars_mixed_together() {
+        assert!(matches("**/*", "dogs/12/walks"));
+        assert!(matches("*/**", "dogs/12/walks"));
+        assert!(matches("*/**/*", "dogs/12/walks"));
+        assert!(!matches("*/**/*", "dogs/12"));
+        assert!(!matches("*/**/*", "dogs"));
+        assert!(matches("**a*", "x/y/ab"));
+        assert!(!matches("**a*", "x/a/b"));
+    }
+
+    #[test]
+    fn exact_names() {
+        assert!(matches("alerts/global", "alerts/global"));
+        assert!(!matches("alerts/global", "alerts/globals"));
+        assert!(!matches("alerts", "alerts/global"));
+    }
+
+    #[test]
+    fn many_stars_stay_fast() {
+        let channel = "a".repeat(60);
+        assert!(!matches("**a**a**a**a**a**a**b", &channel));
+        assert!(!matches("*a*a*a*a*a*a*a*a*a*a*b", &channel));
+        assert!(matches("**a**a**a**a**a**a**", &channel));
+    }
+}
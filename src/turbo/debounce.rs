@@ -0,0 +1,70 @@
+use super::{TurboStream, TurboTarget};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long broadcasts are held before the latest one is sent
+pub(crate) const DEFAULT_WINDOW: Duration = Duration::from_millis(500);
+
+/// Renders the HTML for a broadcast, once its window is over
+pub(crate) type RenderJob =
+    Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = String> + Send>> + Send>;
+
+/// Broadcasts to the same channel and target replace each other while they wait
+type DebounceKey = (String, Option<TurboTarget>);
+
+/// Holds the latest broadcast for each (channel, target) until its window is over
+pub(crate) struct Debouncer {
+    window: Duration,
+    pending: Mutex<HashMap<DebounceKey, RenderJob>>,
+}
+
+impl Debouncer {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Queue a render for the key. The first job for a key starts the window,
+    /// later jobs inside the window replace it without being rendered
+    pub(crate) fn schedule(
+        &self,
+        turbo: &TurboStream,
+        channel: String,
+        target: Option<TurboTarget>,
+        job: RenderJob,
+    ) {
+        let key = (channel, target);
+        let mut pending = self.pending.lock().unwrap();
+        let waiting = pending.contains_key(&key);
+        pending.insert(key.clone(), job);
+        if waiting {
+            return;
+        }
+        let turbo = turbo.clone();
+        let window = self.window;
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            let job = turbo.debouncer.pending.lock().unwrap().remove(&key);
+            if let Some(job) = job {
+                let html = job().await;
+                turbo.stream(key.0, html);
+            }
+        });
+    }
+}
+
+impl Debug for Debouncer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pending = self.pending.lock().map(|p| p.len()).unwrap_or_default();
+        f.debug_struct("Debouncer")
+            .field("window", &self.window)
+            .field("pending", &pending)
+            .finish()
+    }
+}
@@ -0,0 +1,131 @@
+use super::{ChannelPattern, TurboStream};
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use yew::{function_component, html, Html, Properties};
+
+/// Counters kept by a TurboStream as messages flow through it
+#[derive(Debug, Default)]
+pub(crate) struct TurboMetrics {
+    subscribers: Mutex<BTreeMap<String, usize>>,
+    messages_sent: AtomicU64,
+    messages_dropped: AtomicU64,
+    lag_events: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl TurboMetrics {
+    pub(crate) fn subscribed(&self, pattern: &ChannelPattern) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        *subscribers.entry(pattern.to_string()).or_default() += 1;
+    }
+
+    pub(crate) fn unsubscribed(&self, pattern: &ChannelPattern) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let key = pattern.to_string();
+        if let Some(count) = subscribers.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                subscribers.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn sent(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn lagged(&self, dropped: u64) {
+        self.lag_events.fetch_add(1, Ordering::Relaxed);
+        self.messages_dropped.fetch_add(dropped, Ordering::Relaxed);
+    }
+
+    pub(crate) fn written(&self, bytes: usize) {
+        self.bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> TurboStats {
+        let subscribers = self.subscribers.lock().unwrap().clone();
+        TurboStats {
+            total_subscribers: subscribers.values().sum(),
+            subscribers,
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_dropped: self.messages_dropped.load(Ordering::Relaxed),
+            lag_events: self.lag_events.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A snapshot of what a TurboStream has been up to since it was created
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurboStats {
+    /// Open streams watching each channel or channel pattern
+    pub subscribers: BTreeMap<String, usize>,
+    /// Every channel watched by every open stream.
+    /// A stream watching two channels is counted twice
+    pub total_subscribers: usize,
+    /// Messages sent through this TurboStream
+    pub messages_sent: u64,
+    /// Messages subscribers missed because they fell behind
+    pub messages_dropped: u64,
+    /// How many times a subscriber fell behind
+    pub lag_events: u64,
+    /// Bytes written to SSE clients
+    pub bytes_written: u64,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TurboStatsPageProps {
+    pub stats: TurboStats,
+}
+
+/// A status page showing TurboStats
+#[function_component(TurboStatsPage)]
+pub fn turbo_stats_page(props: &TurboStatsPageProps) -> Html {
+    let stats = &props.stats;
+    let channels = stats.subscribers.iter().map(|(channel, count)| {
+        html! {
+            <tr><td>{ channel }</td><td>{ *count }</td></tr>
+        }
+    });
+    html! {
+        <html>
+            <head><title>{ "Turbo Streams" }</title></head>
+            <body>
+                <h1>{ "Turbo Streams" }</h1>
+                <dl>
+                    <dt>{ "Subscribers" }</dt><dd>{ stats.total_subscribers }</dd>
+                    <dt>{ "Messages sent" }</dt><dd>{ stats.messages_sent }</dd>
+                    <dt>{ "Messages dropped" }</dt><dd>{ stats.messages_dropped }</dd>
+                    <dt>{ "Lag events" }</dt><dd>{ stats.lag_events }</dd>
+                    <dt>{ "Bytes written" }</dt><dd>{ stats.bytes_written }</dd>
+                </dl>
+                <table>
+                    <thead><tr><th>{ "Channel" }</th><th>{ "Subscribers" }</th></tr></thead>
+                    <tbody>{ for channels }</tbody>
+                </table>
+            </body>
+        </html>
+    }
+}
+
+/// An actix endpoint that renders the TurboStats of the TurboStream in the app_data.
+///
+/// It shows every channel being watched, so mount it somewhere only admins can reach
+///
+/// ```
+/// use actix_web::{web, App};
+/// use gumbo_lib::turbo::{turbo_stats, TurboStream};
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(TurboStream::default()))
+///     .route("/admin/turbo", web::get().to(turbo_stats));
+/// ```
+pub async fn turbo_stats(turbo: Data<TurboStream>) -> Result<HttpResponse, actix_web::Error> {
+    let stats = turbo.stats();
+    crate::view::render::<TurboStatsPage, _, _>(TurboStatsPageProps { stats }).await
+}
@@ -0,0 +1,775 @@
+mod action;
+pub use crate::respond::TURBO_STREAM_MIME;
+pub use action::{TurboAction, TurboStreamAction, TurboTarget};
+mod backend;
+pub use backend::{LocalBackend, TurboBackend, TurboFanout, TurboPayload};
+mod broadcastable;
+pub use broadcastable::Broadcastable;
+#[cfg(feature = "sessions")]
+mod authorize;
+#[cfg(feature = "sessions")]
+pub use authorize::{authorize_sse, authorized_sse, ChannelAuthorizer};
+mod channel;
+pub use channel::{ChannelMatch, ChannelPattern};
+mod debounce;
+mod metrics;
+pub use metrics::{turbo_stats, TurboStats, TurboStatsPage, TurboStatsPageProps};
+mod origin;
+pub use origin::{
+    use_turbo_connection_id, TurboConnection, TurboConnectionId, TurboConnectionMeta,
+    TurboConnectionProps, TurboRequestId, TURBO_CONNECTION_ID_HEADER, TURBO_CONNECTION_ID_PARAM,
+    TURBO_REQUEST_ID_HEADER,
+};
+mod presence;
+pub use presence::{presence_dom_id, PresenceEvent, PresenceProps};
+mod recording;
+pub use recording::{RecordedAction, RecordingBackend, TurboBroadcasts};
+mod replay;
+pub use replay::{LastEventId, ReplayCursor};
+mod response;
+pub use response::TurboStreamResponse;
+mod signed;
+pub use signed::{
+    sign_stream_name, verify_stream_name, SignedStreamName, TurboCableStreamSource,
+    TurboStreamSource, SIGNED_STREAM_NAME_PARAM,
+};
+mod service;
+pub use service::TurboSseService;
+mod sse;
+pub use sse::{SseEvent, SseGuard, TurboSse};
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub use unix::{TurboBroker, UnixSocketBackend};
+#[cfg(feature = "turbo-websockets")]
+mod websocket;
+#[cfg(feature = "turbo-websockets")]
+pub use websocket::turbo_websocket;
+
+use crate::errors::Result;
+use actix_web::web::Bytes;
+use debounce::{Debouncer, RenderJob};
+use metrics::TurboMetrics;
+use presence::{Presence, PresenceGuard};
+use replay::ReplayLog;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+use yew::html::BaseComponent;
+use yew::ServerRenderer;
+
+#[derive(Debug)]
+pub struct TurboMessage {
+    /// Increases with every message sent, on any channel.
+    /// 0 for messages generated by the stream itself, like a refresh after lagging
+    pub id: u64,
+    /// Changes when the ids start over, for example when the `TurboBroker` restarts
+    pub epoch: u64,
+    pub channel: String,
+    pub html: String,
+    /// The connection that caused this message. Streams for that connection skip it
+    pub origin: Option<String>,
+}
+
+/// A wrapper around tokio::sync::broadcast
+/// It is used to Send out Render Events to anyone who wants them
+///
+/// Recent messages are kept so clients that reconnect with a `Last-Event-ID`
+/// can catch up on what they missed.
+///
+/// Messages are carried by a TurboBackend. By default they only reach
+/// subscribers in this process, see `with_backend` to reach other processes.
+#[derive(Debug, Clone)]
+pub struct TurboStream {
+    fanout: TurboFanout,
+    backend: Arc<dyn TurboBackend>,
+    debouncer: Arc<Debouncer>,
+    shutdown: Arc<watch::Sender<bool>>,
+    presence: Arc<Presence>,
+    metrics: Arc<TurboMetrics>,
+    recorder: Option<Arc<RecordingBackend>>,
+}
+
+/// How many messages a slow subscriber can fall behind before it starts missing them
+const DEFAULT_CAPACITY: usize = 100;
+
+impl Default for TurboStream {
+    fn default() -> Self {
+        TurboStream::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl TurboStream {
+    /// Build a TurboStream that buffers up to `capacity` messages for slow subscribers.
+    /// Subscribers that fall further behind than this will lag and miss messages.
+    /// A capacity of 0 is treated as 1
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (tx, _rx) = tokio::sync::broadcast::channel(capacity.max(1));
+        let fanout = TurboFanout {
+            tx,
+            replay: Arc::default(),
+        };
+        TurboStream::default_backend(fanout)
+    }
+
+    fn default_backend(fanout: TurboFanout) -> Self {
+        let backend = LocalBackend::default();
+        backend.start(fanout.clone());
+        TurboStream {
+            fanout,
+            backend: Arc::new(backend),
+            debouncer: Arc::new(Debouncer::new(debounce::DEFAULT_WINDOW)),
+            shutdown: Arc::new(watch::Sender::new(false)),
+            presence: Arc::default(),
+            metrics: Arc::default(),
+            recorder: None,
+        }
+    }
+
+    /// A TurboStream for tests that records every message it sends.
+    ///
+    /// Give it to your app as `web::Data` like the real one,
+    /// then check what was sent with `broadcasts`
+    pub fn recording() -> Self {
+        let mut turbo = TurboStream::default();
+        let recorder = Arc::new(RecordingBackend::default());
+        recorder.start(turbo.fanout.clone());
+        turbo.backend = recorder.clone();
+        turbo.recorder = Some(recorder);
+        turbo
+    }
+
+    /// Every message sent by a `recording` TurboStream, oldest first.
+    ///
+    /// Panics if this TurboStream isn't recording
+    #[track_caller]
+    pub fn recorded(&self) -> Vec<Arc<TurboMessage>> {
+        match &self.recorder {
+            Some(recorder) => recorder.messages(),
+            None => panic!("TurboStream::recorded called on a TurboStream that isn't recording"),
+        }
+    }
+
+    /// Forget the messages recorded so far
+    #[track_caller]
+    pub fn clear_recorded(&self) {
+        match &self.recorder {
+            Some(recorder) => recorder.clear(),
+            None => {
+                panic!("TurboStream::clear_recorded called on a TurboStream that isn't recording")
+            }
+        }
+    }
+
+    /// The actions sent by a `recording` TurboStream, ready to be filtered and asserted on.
+    ///
+    /// Panics if this TurboStream isn't recording
+    #[track_caller]
+    pub fn broadcasts(&self) -> TurboBroadcasts {
+        TurboBroadcasts::new(&self.recorded())
+    }
+
+    /// Keep up to `capacity` recent messages, across every channel, for up to `ttl`,
+    /// to replay to clients that reconnect. A capacity of 0 disables replay.
+    ///
+    /// Clients that missed more than was kept are sent a Turbo `refresh` instead
+    pub fn with_replay(self, capacity: usize, ttl: Duration) -> Self {
+        self.fanout.replay.lock().unwrap().configure(capacity, ttl);
+        self
+    }
+
+    /// Send messages through a different TurboBackend,
+    /// for example to reach subscribers connected to other processes
+    pub fn with_backend(mut self, backend: impl TurboBackend) -> Self {
+        backend.start(self.fanout.clone());
+        self.backend = Arc::new(backend);
+        self.recorder = None;
+        self
+    }
+
+    /// How long the `*_later` broadcasts wait before sending. Defaults to 500ms
+    pub fn with_debounce(mut self, window: Duration) -> Self {
+        self.debouncer = Arc::new(Debouncer::new(window));
+        self
+    }
+
+    /// Render a Yew view Into the TurboStream Pipeline.
+    /// Its is expected that the HTML contains a TurboStream message.
+    ///
+    /// ```html
+    /// <turbo-stream action=*** target=*** >
+    ///   <template>
+    ///   </template>
+    /// </turbo-stream>
+    /// ```
+    /// This message can then be send out using a SSE action
+    /// and the helper function `turbo_sse_stream`
+    pub async fn render<V, VM>(&self, channel: impl Into<String>, args: VM) -> Result<()>
+    where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        self.render_except::<V, VM>(channel, &TurboConnectionId::default(), args)
+            .await
+    }
+
+    /// Render a Yew view Into the TurboStream Pipeline,
+    /// sending it to everyone but the connection it originated from.
+    /// See `stream_except`
+    pub async fn render_except<V, VM>(
+        &self,
+        channel: impl Into<String>,
+        origin: &TurboConnectionId,
+        args: VM,
+    ) -> Result<()>
+    where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        let renderer = ServerRenderer::<V>::with_props(|| args);
+        let html = renderer.render().await;
+        self.stream_except(channel, html, origin);
+        Ok(())
+    }
+
+    /// Send a typed TurboStreamAction out to everyone watching the channel
+    pub fn broadcast(&self, channel: impl Into<String>, action: &TurboStreamAction) {
+        self.stream(channel, action.to_html());
+    }
+
+    /// Send a typed TurboStreamAction out to everyone watching the channel
+    /// but the connection it originated from. See `stream_except`
+    pub fn broadcast_except(
+        &self,
+        channel: impl Into<String>,
+        action: &TurboStreamAction,
+        origin: &TurboConnectionId,
+    ) {
+        self.stream_except(channel, action.to_html(), origin);
+    }
+
+    /// Ask everyone watching the channel to reload the page,
+    /// except the client that made the request.
+    ///
+    /// The refresh is tagged with the request's `TurboRequestId`,
+    /// and Turbo ignores refreshes caused by requests it made itself.
+    /// Use it after a form submission whose response already updated the page
+    pub fn broadcast_refresh_except(&self, channel: impl Into<String>, origin: &TurboRequestId) {
+        let mut action = TurboStreamAction::refresh();
+        if let Some(id) = origin.id() {
+            action = action.request_id(id);
+        }
+        self.broadcast(channel, &action);
+    }
+
+    /// Send a batch of TurboStreamActions out as one message to everyone watching the channel
+    pub fn broadcast_batch(&self, channel: impl Into<String>, batch: &TurboStreamResponse) {
+        self.stream(channel, batch.to_html());
+    }
+
+    /// Send a TurboStreamAction after the debounce window.
+    ///
+    /// Actions for the same channel and target that are queued inside the window
+    /// replace each other, so only the last one is sent.
+    /// Must be called from inside the tokio/actix runtime
+    pub fn broadcast_later(&self, channel: impl Into<String>, action: TurboStreamAction) {
+        let target = action.turbo_target().cloned();
+        let job: RenderJob = Box::new(move || Box::pin(async move { action.to_html() }));
+        self.debouncer.schedule(self, channel.into(), target, job);
+    }
+
+    /// Render a Yew view into the TurboStreamAction and send it after the debounce window.
+    ///
+    /// Like `broadcast_later`, only the last render for a channel and target inside
+    /// the window is sent. The view is rendered in the background, off the request path,
+    /// and renders that are replaced are never done.
+    pub fn render_action_later<V, VM>(
+        &self,
+        channel: impl Into<String>,
+        action: TurboStreamAction,
+        args: VM,
+    ) where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        let target = action.turbo_target().cloned();
+        let job: RenderJob =
+            Box::new(move || Box::pin(async move { action.render::<V, VM>(args).await.to_html() }));
+        self.debouncer.schedule(self, channel.into(), target, job);
+    }
+
+    /// Ask everyone watching the channel to reload the page, after the debounce window.
+    /// Any number of refreshes inside the window collapse into one
+    pub fn broadcast_refresh_later(&self, channel: impl Into<String>) {
+        self.broadcast_later(channel, TurboStreamAction::refresh());
+    }
+
+    pub fn stream(&self, channel: impl Into<String>, html: impl Into<String>) {
+        self.stream_except(channel, html, &TurboConnectionId::default());
+    }
+
+    /// Send HTML to everyone watching the channel, except the streams
+    /// of the connection it originated from.
+    ///
+    /// The origin is the `TurboConnectionId` of the request that made the change,
+    /// since that page already has the change from its response.
+    /// Streams only know their connection when given one with `for_connection`
+    pub fn stream_except(
+        &self,
+        channel: impl Into<String>,
+        html: impl Into<String>,
+        origin: &TurboConnectionId,
+    ) {
+        self.metrics.sent();
+        self.backend.publish(TurboPayload {
+            epoch: 0,
+            id: 0,
+            channel: channel.into(),
+            html: html.into(),
+            origin: origin.id().map(|id| id.to_owned()),
+        });
+    }
+
+    /// Watch a single channel or channel pattern, for example `dogs/create` or `dogs/*`
+    pub fn watch(&self, channel: impl Into<ChannelPattern>) -> TurboMessageStream {
+        self.watch_many([channel])
+    }
+
+    /// Watch many channels and channel patterns with a single stream
+    pub fn watch_many<I, C>(&self, channels: I) -> TurboMessageStream
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<ChannelPattern>,
+    {
+        let patterns: Vec<ChannelPattern> = channels.into_iter().map(|c| c.into()).collect();
+        patterns.iter().for_each(|p| self.metrics.subscribed(p));
+        let replay = self.fanout.replay.lock().unwrap();
+        let cursor = replay.cursor();
+        TurboMessageStream {
+            patterns,
+            rx: self.fanout.tx.subscribe(),
+            replay: self.fanout.replay.clone(),
+            cursor,
+            pending: VecDeque::default(),
+            on_lag: None,
+            dropped: 0,
+            shutdown: self.shutdown.subscribe(),
+            presence: None,
+            metrics: self.metrics.clone(),
+            connection: TurboConnectionId::default(),
+        }
+    }
+
+    /// Subscriber counts and message totals, see `turbo_stats` for a status page
+    pub fn stats(&self) -> TurboStats {
+        self.metrics.stats()
+    }
+
+    /// Watch a channel as the given identity, usually the session `sub`.
+    ///
+    /// The identity is one of the channel's `members` until the stream is dropped.
+    /// When `with_presence` is used, everyone on the channel is sent an update as they come and go.
+    ///
+    /// Presence is only tracked for streams watching through this process.
+    pub fn watch_as(
+        &self,
+        channel: impl Into<String>,
+        identity: impl Into<String>,
+    ) -> TurboMessageStream {
+        let channel: String = channel.into();
+        // subscribe first so the new member sees their own arrival
+        let mut stream = self.watch(ChannelPattern::exact(channel.as_str()));
+        let guard = self.presence.join(self, channel, identity.into());
+        stream.presence = Some(guard);
+        stream
+    }
+
+    /// Everyone watching the channel with `watch_as`
+    pub fn members(&self, channel: &str) -> Vec<String> {
+        self.presence.members(channel)
+    }
+
+    /// Send an update rendered with the view V to everyone on a channel
+    /// as members join or leave it.
+    ///
+    /// The update replaces the contents of the element with the id `presence_dom_id(channel)`
+    ///
+    /// ```
+    /// use gumbo_lib::turbo::{PresenceProps, TurboStream};
+    /// use yew::{function_component, html, Html};
+    ///
+    /// #[function_component(Viewers)]
+    /// fn viewers(props: &PresenceProps) -> Html {
+    ///     html! { <span>{ format!("{} people are viewing this", props.members.len()) }</span> }
+    /// }
+    ///
+    /// let turbo = TurboStream::default().with_presence::<Viewers>();
+    /// ```
+    pub fn with_presence<V>(self) -> Self
+    where
+        V: BaseComponent<Properties = PresenceProps>,
+    {
+        self.presence.render_with::<V>();
+        self
+    }
+
+    /// Close every TurboMessageStream watching this TurboStream,
+    /// ending their SSE and WebSocket connections.
+    ///
+    /// Call this when the server is stopping, so open streams don't hold up a graceful shutdown
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    /// True once `shutdown` has been called
+    pub fn is_shutdown(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+}
+
+/// Called when a TurboMessageStream falls behind and misses messages.
+/// Receives the number of messages that were dropped,
+/// and returns the HTML (if any) to send to the client so it can resync.
+pub type LagHandler = Arc<dyn Fn(u64) -> Option<String> + Send + Sync>;
+
+pub struct TurboMessageStream {
+    patterns: Vec<ChannelPattern>,
+    rx: Receiver<Arc<TurboMessage>>,
+    replay: Arc<Mutex<ReplayLog>>,
+    cursor: ReplayCursor,
+    pending: VecDeque<ChannelMatch>,
+    on_lag: Option<LagHandler>,
+    dropped: u64,
+    shutdown: watch::Receiver<bool>,
+    presence: Option<PresenceGuard>,
+    metrics: Arc<TurboMetrics>,
+    connection: TurboConnectionId,
+}
+
+impl TurboMessageStream {
+    /// return the next TurboMessage for your channels
+    pub async fn next(&mut self) -> Option<Arc<TurboMessage>> {
+        let matched = self.next_match().await?;
+        Some(matched.message)
+    }
+
+    /// return the next TurboMessage for your channels along with the pattern that matched it
+    pub async fn next_match(&mut self) -> Option<ChannelMatch> {
+        if let Some(matched) = self.pending.pop_front() {
+            if matched.message.id > 0 {
+                self.cursor = ReplayCursor::new(matched.message.epoch, matched.message.id);
+            }
+            return Some(matched);
+        }
+        loop {
+            let received = tokio::select! {
+                received = self.rx.recv() => received,
+                _ = self.shutdown.wait_for(|stopped| *stopped) => return None,
+            };
+            let msg = match received {
+                Ok(msg) => msg,
+                Err(RecvError::Closed) => return None,
+                Err(RecvError::Lagged(count)) => {
+                    log::warn!("TurboMessageStream lagged, dropped {count} messages");
+                    self.dropped += count;
+                    self.metrics.lagged(count);
+                    match self.lagged(count) {
+                        Some(matched) => return Some(matched),
+                        None => continue,
+                    }
+                }
+            };
+            let pattern = self
+                .patterns
+                .iter()
+                .find(|p| p.matches(&msg.channel))
+                .cloned();
+            if msg.epoch != self.cursor.epoch() {
+                let Some(pattern) = pattern else {
+                    continue;
+                };
+                // The ids started over, so what this client has seen can't be compared to them.
+                // A client that had seen messages is refreshed instead, it may have missed some
+                let seen_any = !self.cursor.is_empty();
+                self.cursor = ReplayCursor::new(msg.epoch, msg.id);
+                if seen_any {
+                    log::debug!("TurboMessageStream message ids started over, refreshing");
+                    match self.refresh() {
+                        Some(matched) => return Some(matched),
+                        None => continue,
+                    }
+                }
+                if self.is_own(&msg) {
+                    continue;
+                }
+                return Some(ChannelMatch {
+                    pattern,
+                    message: msg,
+                });
+            }
+            if let Some(pattern) = pattern {
+                // already sent to the client when replaying
+                if msg.id <= self.cursor.id() {
+                    continue;
+                }
+                self.cursor = ReplayCursor::new(msg.epoch, msg.id);
+                if self.is_own(&msg) {
+                    continue;
+                }
+                return Some(ChannelMatch {
+                    pattern,
+                    message: msg,
+                });
+            }
+        }
+    }
+
+    /// Catch up on the messages a reconnecting client missed.
+    /// Pass in the `Last-Event-ID` the browser sent.
+    ///
+    /// If the missed messages are no longer buffered a Turbo `refresh` action is sent instead
+    pub fn resume(mut self, last_event_id: Option<&str>) -> Self {
+        let Some(cursor) = last_event_id.and_then(|id| id.parse::<ReplayCursor>().ok()) else {
+            return self;
+        };
+        let replay = self.replay.lock().unwrap().since(&cursor, &self.patterns);
+        if replay.gap {
+            log::debug!("TurboMessageStream::resume missed messages are gone, refreshing");
+            if let Some(matched) = self.refresh() {
+                self.pending.push_back(matched);
+            }
+            return self;
+        }
+        for msg in replay.messages {
+            let Some(pattern) = self.patterns.iter().find(|p| p.matches(&msg.channel)) else {
+                continue;
+            };
+            if self.is_own(&msg) {
+                continue;
+            }
+            self.pending.push_back(ChannelMatch {
+                pattern: pattern.clone(),
+                message: msg,
+            });
+        }
+        self
+    }
+
+    /// The id of the last message this stream delivered.
+    /// Use it as the SSE event id so the browser can resume where it left off
+    pub fn cursor(&self) -> &ReplayCursor {
+        &self.cursor
+    }
+
+    /// Builds the message sent to the client after this stream lagged behind.
+    /// By default this is a Turbo `refresh` action so the page resyncs itself
+    fn lagged(&self, count: u64) -> Option<ChannelMatch> {
+        let html = match &self.on_lag {
+            Some(handler) => handler(count)?,
+            None => TurboStreamAction::refresh().to_html(),
+        };
+        self.generated(html)
+    }
+
+    /// A Turbo `refresh` action, for when the client can't be caught up any other way
+    fn refresh(&self) -> Option<ChannelMatch> {
+        self.generated(TurboStreamAction::refresh().to_html())
+    }
+
+    /// A message made by this stream rather than sent by anyone
+    fn generated(&self, html: String) -> Option<ChannelMatch> {
+        let pattern = self.patterns.first()?.clone();
+        let message = Arc::new(TurboMessage {
+            id: 0,
+            epoch: 0,
+            channel: pattern.to_string(),
+            html,
+            origin: None,
+        });
+        Some(ChannelMatch { pattern, message })
+    }
+
+    /// Replace what is sent to the client when this stream falls behind and misses messages.
+    /// Return None from the handler to silently skip the missed messages
+    pub fn on_lag(
+        mut self,
+        handler: impl Fn(u64) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.on_lag = Some(Arc::new(handler));
+        self
+    }
+
+    /// Skip messages sent with `stream_except` by this connection,
+    /// the page already has them from its own responses
+    pub fn for_connection(mut self, connection: &TurboConnectionId) -> Self {
+        self.set_connection(connection);
+        self
+    }
+
+    /// Set the connection this stream is for, see `for_connection`
+    pub fn set_connection(&mut self, connection: &TurboConnectionId) {
+        self.connection = connection.clone();
+    }
+
+    /// True if the message was caused by the connection this stream is for
+    fn is_own(&self, msg: &TurboMessage) -> bool {
+        self.connection.id().is_some() && msg.origin.as_deref() == self.connection.id()
+    }
+
+    /// The number of messages this stream has missed because it fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Start receiving messages for another channel or channel pattern
+    pub fn subscribe(&mut self, channel: impl Into<ChannelPattern>) {
+        let pattern = channel.into();
+        if !self.patterns.contains(&pattern) {
+            self.metrics.subscribed(&pattern);
+            self.patterns.push(pattern);
+        }
+    }
+
+    /// Stop receiving messages for a channel or channel pattern.
+    /// Returns false if the stream wasn't subscribed to it
+    pub fn unsubscribe(&mut self, channel: impl Into<ChannelPattern>) -> bool {
+        let pattern = channel.into();
+        let before = self.patterns.len();
+        self.patterns.retain(|p| p != &pattern);
+        let removed = before - self.patterns.len();
+        (0..removed).for_each(|_| self.metrics.unsubscribed(&pattern));
+        removed > 0
+    }
+
+    /// The channels and channel patterns this stream is watching
+    pub fn channels(&self) -> &[ChannelPattern] {
+        &self.patterns
+    }
+
+    /// Count bytes sent to the client in the TurboStats
+    pub(crate) fn written(&self, bytes: usize) {
+        self.metrics.written(bytes);
+    }
+}
+
+impl Drop for TurboMessageStream {
+    fn drop(&mut self) {
+        self.patterns
+            .iter()
+            .for_each(|p| self.metrics.unsubscribed(p));
+    }
+}
+
+/// The inner logic for a futures::unfold()
+/// Used to stream turbo SSE to the frontend
+///
+/// Prefer `TurboSseService`, or `TurboSse` for a custom endpoint,
+/// which also send heartbeats while the stream is idle.
+///
+/// In your controller action your will need to build a futures::stream
+/// to send out turbo changes.
+///
+/// ```
+/// use gumbo_lib::turbo::{TurboStream, turbo_sse_stream};
+/// use actix_web::web::Data;
+/// use actix_web::HttpResponse;
+/// use futures::stream::unfold;
+///
+/// // actix endpoint
+/// pub(crate) async fn stream(turbo: Data<TurboStream>) -> Result<HttpResponse, ()> {
+///   let sub = turbo.watch("dogs/create");
+///   let body = unfold(sub, turbo_sse_stream);
+///   Ok(HttpResponse::Ok().content_type("text/event-stream").streaming(body))
+/// }
+/// ```
+///
+pub async fn turbo_sse_stream(
+    mut state: TurboMessageStream,
+) -> Option<(
+    std::result::Result<Bytes, actix_web::Error>,
+    TurboMessageStream,
+)> {
+    let msg = state.next().await?;
+    let cursor = state.cursor().to_string();
+    let bytes = SseEvent::new(msg.html.as_str()).id(cursor).to_bytes();
+    state.written(bytes.len());
+    Some((Ok::<_, actix_web::Error>(bytes), state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn next_html(stream: &mut TurboMessageStream) -> String {
+        let next = tokio::time::timeout(Duration::from_secs(1), stream.next());
+        next.await.unwrap().unwrap().html.clone()
+    }
+
+    #[tokio::test]
+    async fn resume_replays_missed_messages_once() {
+        let turbo = TurboStream::default();
+        let mut first = turbo.watch("dogs");
+        turbo.stream("dogs", "1");
+        assert_eq!(next_html(&mut first).await, "1");
+        let last_event_id = first.cursor().to_string();
+        drop(first);
+
+        turbo.stream("dogs", "2");
+        // sent after the new stream subscribed, so it is in both the replay and the live stream
+        let stream = turbo.watch("dogs");
+        turbo.stream("cats", "ignored");
+        turbo.stream("dogs", "3");
+        let mut stream = stream.resume(Some(&last_event_id));
+        assert_eq!(next_html(&mut stream).await, "2");
+        assert_eq!(next_html(&mut stream).await, "3");
+        turbo.stream("dogs", "4");
+        assert_eq!(next_html(&mut stream).await, "4");
+    }
+
+    #[tokio::test]
+    async fn resume_refreshes_when_the_cursor_is_unknown() {
+        let turbo = TurboStream::default();
+        let stream = turbo.watch("dogs");
+        turbo.stream("dogs", "1");
+        let future = ReplayCursor::new(stream.cursor().epoch(), 99).to_string();
+        let mut stream = stream.resume(Some(&future));
+        assert!(next_html(&mut stream).await.contains(r#"action="refresh""#));
+    }
+
+    #[tokio::test]
+    async fn streams_skip_messages_from_their_own_connection() {
+        let turbo = TurboStream::default();
+        let page = TurboConnectionId::from_id("page-1");
+        let mut own = turbo.watch("dogs").for_connection(&page);
+        let mut other = turbo.watch("dogs");
+        let last_event_id = own.cursor().to_string();
+        turbo.stream_except("dogs", "from page-1", &page);
+        turbo.stream("dogs", "from the server");
+        assert_eq!(next_html(&mut other).await, "from page-1");
+        assert_eq!(next_html(&mut other).await, "from the server");
+        assert_eq!(next_html(&mut own).await, "from the server");
+
+        // a reconnecting page isn't replayed its own messages either
+        let mut resumed = turbo
+            .watch("dogs")
+            .for_connection(&page)
+            .resume(Some(&last_event_id));
+        assert_eq!(next_html(&mut resumed).await, "from the server");
+        turbo.stream("dogs", "later");
+        assert_eq!(next_html(&mut resumed).await, "later");
+    }
+
+    #[tokio::test]
+    async fn resume_ignores_a_missing_or_invalid_last_event_id() {
+        let turbo = TurboStream::default();
+        let mut stream = turbo.watch("dogs").resume(None).resume(Some("nonsense"));
+        turbo.stream("dogs", "1");
+        assert_eq!(next_html(&mut stream).await, "1");
+    }
+}
@@ -0,0 +1,240 @@
+use actix_web::dev::Payload;
+use actix_web::web::Query;
+use actix_web::{FromRequest, HttpRequest};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+use yew::{function_component, html, use_context, Children, ContextProvider, Html, Properties};
+
+/// The header Turbo sends with every fetch it makes
+pub const TURBO_REQUEST_ID_HEADER: &str = "X-Turbo-Request-Id";
+
+/// The `X-Turbo-Request-Id` header of the request, used to keep a client
+/// from reloading because of its own change.
+///
+/// Turbo sends a new id with every fetch and remembers the ones it made recently.
+/// A `refresh` tagged with one of those ids is ignored by the browser that made the request,
+/// see `TurboStream::broadcast_refresh_except` and `TurboStreamAction::request_id`.
+///
+/// Only refreshes can be de-duplicated this way.
+/// Other actions can skip the page that made the change with its `TurboConnectionId`.
+///
+/// ```
+/// use actix_web::web::Data;
+/// use actix_web::HttpResponse;
+/// use gumbo_lib::turbo::{TurboRequestId, TurboStream, TurboStreamAction};
+///
+/// pub(crate) async fn create(turbo: Data<TurboStream>, origin: TurboRequestId) -> HttpResponse {
+///     // everyone else watching "dogs" reloads, this browser keeps its response
+///     turbo.broadcast_refresh_except("dogs", &origin);
+///     let action = TurboStreamAction::append("dogs").html("<li>Rex</li>");
+///     HttpResponse::Ok().body(action.to_html())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurboRequestId(Option<String>);
+
+impl TurboRequestId {
+    pub fn id(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+/// Allows you to request the TurboRequestId from an actix resource
+impl FromRequest for TurboRequestId {
+    type Error = actix_web::Error;
+    type Future = Ready<std::result::Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let id = req
+            .headers()
+            .get(TURBO_REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_owned());
+        ready(Ok(TurboRequestId(id)))
+    }
+}
+
+/// The header `TurboConnectionMeta` adds to every fetch Turbo makes
+pub const TURBO_CONNECTION_ID_HEADER: &str = "X-Turbo-Connection-Id";
+
+/// The query parameter `TurboStreamSource` adds to the SSE url,
+/// and the subscription param `TurboCableStreamSource` sends over the WebSocket
+pub const TURBO_CONNECTION_ID_PARAM: &str = "connection_id";
+
+/// Sends the connection id from the page's meta tag with every fetch Turbo makes
+const CONNECTION_SCRIPT: &str = r#"if(!window.gumboTurboConnection){window.gumboTurboConnection=true;document.addEventListener("turbo:before-fetch-request",function(e){var m=document.querySelector('meta[name="turbo-connection-id"]');if(m){e.detail.fetchOptions.headers["X-Turbo-Connection-Id"]=m.content}})}"#;
+
+/// Identifies a single page view, so the changes it makes aren't streamed back to it.
+///
+/// The page is given an id by `TurboConnection`. It is sent with the SSE or WebSocket
+/// subscription, and by `TurboConnectionMeta` as a header on every form submission.
+/// Pass the id of a request to `TurboStream::stream_except` (or `broadcast_except`,
+/// `render_except`) and the streams given the same id with `for_connection` skip the message.
+///
+/// ```
+/// use actix_web::web::Data;
+/// use actix_web::HttpResponse;
+/// use gumbo_lib::turbo::{SignedStreamName, TurboConnectionId, TurboSse, TurboStream};
+/// use gumbo_lib::turbo::TurboStreamAction;
+///
+/// // route: /streams/{signed_stream_name}
+/// pub(crate) async fn stream(
+///     turbo: Data<TurboStream>,
+///     name: SignedStreamName,
+///     connection: TurboConnectionId,
+/// ) -> HttpResponse {
+///     TurboSse::new(name.watch(&turbo).for_connection(&connection)).into_response()
+/// }
+///
+/// pub(crate) async fn create(turbo: Data<TurboStream>, origin: TurboConnectionId) -> HttpResponse {
+///     // everyone else watching "dogs" gets the new dog, this page gets it from the response
+///     let action = TurboStreamAction::append("dogs").html("<li>Rex</li>");
+///     turbo.broadcast_except("dogs", &action, &origin);
+///     HttpResponse::Ok().body(action.to_html())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurboConnectionId(Option<String>);
+
+impl TurboConnectionId {
+    /// A new random id for a page
+    pub fn generate() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let count = NEXT.fetch_add(1, Ordering::Relaxed);
+        let high = RandomState::new().hash_one((SystemTime::now(), count));
+        let low = RandomState::new().hash_one((high, count));
+        TurboConnectionId(Some(format!("{high:016x}{low:016x}")))
+    }
+
+    /// An id sent by the page. Empty ids are ignored
+    pub fn from_id(id: impl Into<String>) -> Self {
+        TurboConnectionId(Some(id.into()).filter(|id| !id.is_empty()))
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+/// Allows you to request the TurboConnectionId from an actix resource.
+/// Read from the `X-Turbo-Connection-Id` header, or the `connection_id` query parameter
+/// since an EventSource can't send headers
+impl FromRequest for TurboConnectionId {
+    type Error = actix_web::Error;
+    type Future = Ready<std::result::Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let header = req
+            .headers()
+            .get(TURBO_CONNECTION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+        let id = header.or_else(|| {
+            let mut query =
+                Query::<HashMap<String, String>>::from_query(req.query_string()).ok()?;
+            query.0.remove(TURBO_CONNECTION_ID_PARAM)
+        });
+        ready(Ok(id.map(TurboConnectionId::from_id).unwrap_or_default()))
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TurboConnectionProps {
+    /// The id of this page. A new one is generated when not given.
+    /// When a layout is rendered separately from the page, give both the same id
+    #[prop_or_else(TurboConnectionId::generate)]
+    pub id: TurboConnectionId,
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// Gives the page a `TurboConnectionId`, used by `TurboConnectionMeta`,
+/// `TurboStreamSource` and `TurboCableStreamSource` inside of it
+#[function_component(TurboConnection)]
+pub fn turbo_connection(props: &TurboConnectionProps) -> Html {
+    html! {
+        <ContextProvider<TurboConnectionId> context={ props.id.clone() }>
+            { props.children.clone() }
+        </ContextProvider<TurboConnectionId>>
+    }
+}
+
+/// Returns the id given to the page by a parent `TurboConnection`, if there is one
+#[yew::hook]
+pub fn use_turbo_connection_id() -> Option<TurboConnectionId> {
+    use_context::<TurboConnectionId>().filter(|c| c.id().is_some())
+}
+
+/// Place in the `<head>` to send the page's `TurboConnectionId` with every form submission.
+/// Renders nothing outside of a `TurboConnection`
+#[function_component(TurboConnectionMeta)]
+pub fn turbo_connection_meta() -> Html {
+    let Some(connection) = use_turbo_connection_id() else {
+        return Html::default();
+    };
+    html! {
+        <>
+            <meta name="turbo-connection-id" content={ connection.id().map(|id| id.to_owned()) } />
+            <script>{ CONNECTION_SCRIPT }</script>
+        </>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[actix_web::test]
+    async fn connection_ids_are_read_from_the_header_or_query() {
+        let req = TestRequest::default()
+            .insert_header((TURBO_CONNECTION_ID_HEADER, "abc"))
+            .to_http_request();
+        let connection = TurboConnectionId::extract(&req).await.unwrap();
+        assert_eq!(connection.id(), Some("abc"));
+
+        let req = TestRequest::with_uri("/streams/dogs?connection_id=def").to_http_request();
+        let connection = TurboConnectionId::extract(&req).await.unwrap();
+        assert_eq!(connection.id(), Some("def"));
+
+        let req = TestRequest::with_uri("/streams/dogs?connection_id=").to_http_request();
+        let connection = TurboConnectionId::extract(&req).await.unwrap();
+        assert_eq!(connection.id(), None);
+    }
+
+    #[test]
+    fn generated_ids_are_unique() {
+        let a = TurboConnectionId::generate();
+        let b = TurboConnectionId::generate();
+        assert_ne!(a, b);
+        assert_eq!(a.id().unwrap().len(), 32);
+    }
+
+    #[function_component(Page)]
+    fn page() -> Html {
+        html! {
+            <TurboConnection id={ TurboConnectionId::from_id("abc") }>
+                <TurboConnectionMeta />
+            </TurboConnection>
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_meta_tag_sends_the_id_with_turbo_fetches() {
+        let html = yew::ServerRenderer::<Page>::new()
+            .hydratable(false)
+            .render()
+            .await;
+        assert!(html.contains(r#"<meta name="turbo-connection-id" content="abc">"#));
+        assert!(html.contains(r#"headers["X-Turbo-Connection-Id"]=m.content"#));
+        let empty = yew::ServerRenderer::<TurboConnectionMeta>::new()
+            .hydratable(false)
+            .render()
+            .await;
+        assert_eq!(empty, "");
+    }
+}
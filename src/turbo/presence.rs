@@ -0,0 +1,198 @@
+use super::{TurboStream, TurboStreamAction};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use yew::html::BaseComponent;
+use yew::Properties;
+
+/// Someone arriving on or leaving a channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresenceEvent {
+    Joined(String),
+    Left(String),
+}
+
+/// Given to the presence view every time someone joins or leaves a channel
+#[derive(Properties, Debug, Clone, PartialEq, Eq)]
+pub struct PresenceProps {
+    pub channel: String,
+    /// Everyone watching the channel, after the event
+    pub members: Vec<String>,
+    pub event: PresenceEvent,
+}
+
+/// The DOM id that presence updates for a channel are sent to
+///
+/// ```
+/// use gumbo_lib::turbo::presence_dom_id;
+///
+/// assert_eq!(presence_dom_id("tickets/12"), "presence_tickets_12");
+/// ```
+pub fn presence_dom_id(channel: &str) -> String {
+    let channel: String = channel
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("presence_{channel}")
+}
+
+type PresenceRenderer =
+    Arc<dyn Fn(PresenceProps) -> Pin<Box<dyn Future<Output = String> + Send>> + Send + Sync>;
+
+/// Who is watching each channel.
+/// An identity watching from many tabs is only counted once
+#[derive(Default)]
+pub(crate) struct Presence {
+    channels: Mutex<HashMap<String, BTreeMap<String, usize>>>,
+    renderer: Mutex<Option<PresenceRenderer>>,
+    /// Announcements waiting to be sent on each channel, the first one is being rendered
+    announcing: Mutex<HashMap<String, VecDeque<PresenceProps>>>,
+}
+
+impl Presence {
+    /// Render join and leave events with the view V
+    pub(crate) fn render_with<V>(&self)
+    where
+        V: BaseComponent<Properties = PresenceProps>,
+    {
+        let renderer: PresenceRenderer = Arc::new(|props: PresenceProps| {
+            let action = TurboStreamAction::update(presence_dom_id(&props.channel));
+            Box::pin(async move { action.render::<V, PresenceProps>(props).await.to_html() })
+        });
+        *self.renderer.lock().unwrap() = Some(renderer);
+    }
+
+    pub(crate) fn members(&self, channel: &str) -> Vec<String> {
+        let channels = self.channels.lock().unwrap();
+        match channels.get(channel) {
+            Some(members) => members.keys().cloned().collect(),
+            None => Vec::default(),
+        }
+    }
+
+    pub(crate) fn join(
+        &self,
+        turbo: &TurboStream,
+        channel: String,
+        identity: String,
+    ) -> PresenceGuard {
+        let joined = {
+            let mut channels = self.channels.lock().unwrap();
+            let count = channels
+                .entry(channel.clone())
+                .or_default()
+                .entry(identity.clone())
+                .or_default();
+            *count += 1;
+            *count == 1
+        };
+        if joined {
+            self.announce(turbo, &channel, PresenceEvent::Joined(identity.clone()));
+        }
+        PresenceGuard {
+            turbo: turbo.clone(),
+            channel,
+            identity,
+        }
+    }
+
+    fn leave(&self, turbo: &TurboStream, channel: &str, identity: &str) {
+        let left = {
+            let mut channels = self.channels.lock().unwrap();
+            let Some(members) = channels.get_mut(channel) else {
+                return;
+            };
+            let left = match members.get_mut(identity) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    false
+                }
+                Some(_) => members.remove(identity).is_some(),
+                None => false,
+            };
+            if members.is_empty() {
+                channels.remove(channel);
+            }
+            left
+        };
+        if left {
+            self.announce(turbo, channel, PresenceEvent::Left(identity.to_owned()));
+        }
+    }
+
+    /// Render the event in the background and send it to everyone on the channel.
+    ///
+    /// Events on a channel are sent one at a time in the order they happened,
+    /// so the last update a client receives always has the current members
+    fn announce(&self, turbo: &TurboStream, channel: &str, event: PresenceEvent) {
+        let Some(renderer) = self.renderer.lock().unwrap().clone() else {
+            return;
+        };
+        // streams can be dropped after the runtime is gone
+        if tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        let props = PresenceProps {
+            channel: channel.to_owned(),
+            members: self.members(channel),
+            event,
+        };
+        let mut announcing = self.announcing.lock().unwrap();
+        let queue = announcing.entry(channel.to_owned()).or_default();
+        queue.push_back(props);
+        if queue.len() > 1 {
+            // already being sent by the task below
+            return;
+        }
+        let turbo = turbo.clone();
+        let channel = channel.to_owned();
+        tokio::spawn(async move {
+            let presence = turbo.presence.clone();
+            loop {
+                let props = match presence.announcing.lock().unwrap().get(&channel) {
+                    Some(queue) => queue.front().cloned(),
+                    None => None,
+                };
+                let Some(props) = props else {
+                    return;
+                };
+                let html = renderer(props).await;
+                turbo.stream(channel.as_str(), html);
+                let mut announcing = presence.announcing.lock().unwrap();
+                let Some(queue) = announcing.get_mut(&channel) else {
+                    return;
+                };
+                queue.pop_front();
+                if queue.is_empty() {
+                    announcing.remove(&channel);
+                    return;
+                }
+            }
+        });
+    }
+}
+
+impl Debug for Presence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let channels = self.channels.lock().map(|c| c.len()).unwrap_or_default();
+        f.debug_struct("Presence")
+            .field("channels", &channels)
+            .finish()
+    }
+}
+
+/// Keeps an identity on a channel's member list. They leave when it is dropped
+pub(crate) struct PresenceGuard {
+    turbo: TurboStream,
+    channel: String,
+    identity: String,
+}
+
+impl Drop for PresenceGuard {
+    fn drop(&mut self) {
+        let presence = self.turbo.presence.clone();
+        presence.leave(&self.turbo, &self.channel, &self.identity);
+    }
+}
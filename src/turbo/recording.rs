@@ -0,0 +1,227 @@
+use super::{TurboAction, TurboBackend, TurboFanout, TurboMessage, TurboPayload};
+use regex::Regex;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A TurboBackend that keeps every message it sends, for use in tests.
+/// Messages are still delivered to subscribers in this process.
+///
+/// Build one with `TurboStream::recording`
+#[derive(Debug, Default)]
+pub struct RecordingBackend {
+    fanout: OnceLock<TurboFanout>,
+    messages: Mutex<Vec<Arc<TurboMessage>>>,
+}
+
+impl RecordingBackend {
+    /// Every message sent so far, oldest first
+    pub fn messages(&self) -> Vec<Arc<TurboMessage>> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    /// Forget the messages sent so far
+    pub fn clear(&self) {
+        self.messages.lock().unwrap().clear();
+    }
+}
+
+impl TurboBackend for RecordingBackend {
+    fn start(&self, fanout: TurboFanout) {
+        let _ = self.fanout.set(fanout);
+    }
+
+    fn publish(&self, payload: TurboPayload) {
+        let Some(fanout) = self.fanout.get() else {
+            log::warn!("TurboStream Error: RecordingBackend was never started");
+            return;
+        };
+        let msg = fanout.deliver(payload);
+        self.messages.lock().unwrap().push(msg);
+    }
+}
+
+/// A single `<turbo-stream>` action found in a recorded message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedAction {
+    pub channel: String,
+    /// None when the message wasn't a `<turbo-stream>`
+    pub action: Option<String>,
+    /// The `target` or `targets` attribute
+    pub target: Option<String>,
+    /// The contents of the `<template>`, or the whole message when it wasn't a `<turbo-stream>`
+    pub html: String,
+}
+
+impl Display for RecordedAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let action = self.action.as_deref().unwrap_or("(raw)");
+        let target = self.target.as_deref().unwrap_or("-");
+        write!(f, "{} {action} {target}: {}", self.channel, self.html)
+    }
+}
+
+/// The actions recorded by a TurboStream, narrowed down with filters and checked with asserts.
+///
+/// ```
+/// use gumbo_lib::turbo::{TurboAction, TurboStream, TurboStreamAction};
+///
+/// let turbo = TurboStream::recording();
+/// // hand turbo.clone() to your app as web::Data and call your endpoint
+/// turbo.broadcast("dogs", &TurboStreamAction::append("dogs").html("<li>Rex</li>"));
+///
+/// turbo
+///     .broadcasts()
+///     .on("dogs")
+///     .with_action(TurboAction::Append)
+///     .with_target("dogs")
+///     .containing("Rex")
+///     .assert_count(1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurboBroadcasts {
+    actions: Vec<RecordedAction>,
+}
+
+impl TurboBroadcasts {
+    pub(crate) fn new(messages: &[Arc<TurboMessage>]) -> Self {
+        let actions = messages.iter().flat_map(|msg| parse(msg)).collect();
+        Self { actions }
+    }
+
+    /// Only actions sent on the channel
+    pub fn on(self, channel: &str) -> Self {
+        self.filter(|a| a.channel == channel)
+    }
+
+    pub fn with_action(self, action: TurboAction) -> Self {
+        self.filter(|a| a.action.as_deref() == Some(action.as_str()))
+    }
+
+    /// Only actions with the given `target` or `targets`
+    pub fn with_target(self, target: &str) -> Self {
+        self.filter(|a| a.target.as_deref() == Some(target))
+    }
+
+    /// Only actions whose template contains the text
+    pub fn containing(self, text: &str) -> Self {
+        self.filter(|a| a.html.contains(text))
+    }
+
+    pub fn filter(mut self, keep: impl Fn(&RecordedAction) -> bool) -> Self {
+        self.actions.retain(|a| keep(a));
+        self
+    }
+
+    pub fn actions(&self) -> &[RecordedAction] {
+        &self.actions
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Panics unless exactly `count` actions are left
+    #[track_caller]
+    pub fn assert_count(&self, count: usize) -> &Self {
+        if self.len() != count {
+            panic!(
+                "expected {count} turbo broadcasts, found {}\n{self}",
+                self.len()
+            );
+        }
+        self
+    }
+
+    /// Panics if no actions are left
+    #[track_caller]
+    pub fn assert_any(&self) -> &Self {
+        if self.is_empty() {
+            panic!("expected a turbo broadcast, found none");
+        }
+        self
+    }
+
+    /// Panics if any actions are left
+    #[track_caller]
+    pub fn assert_none(&self) -> &Self {
+        if !self.is_empty() {
+            panic!("expected no turbo broadcasts, found {}\n{self}", self.len());
+        }
+        self
+    }
+}
+
+impl Display for TurboBroadcasts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for action in &self.actions {
+            writeln!(f, "  {action}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a message into the `<turbo-stream>` actions inside of it
+fn parse(msg: &TurboMessage) -> Vec<RecordedAction> {
+    let streams = Regex::new(r"(?s)<turbo-stream\b([^>]*)>(.*?)</turbo-stream>").unwrap();
+    let actions: Vec<RecordedAction> = streams
+        .captures_iter(&msg.html)
+        .map(|caps| {
+            let attrs = &caps[1];
+            let body = caps[2].trim();
+            let html = body
+                .strip_prefix("<template>")
+                .and_then(|b| b.strip_suffix("</template>"))
+                .unwrap_or(body);
+            RecordedAction {
+                channel: msg.channel.clone(),
+                action: attr(attrs, "action"),
+                target: attr(attrs, "target").or_else(|| attr(attrs, "targets")),
+                html: html.to_owned(),
+            }
+        })
+        .collect();
+    if !actions.is_empty() {
+        return actions;
+    }
+    vec![RecordedAction {
+        channel: msg.channel.clone(),
+        action: None,
+        target: None,
+        html: msg.html.clone(),
+    }]
+}
+
+/// Reads a double quoted attribute, undoing the escaping done when it was written
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    // must start the attribute, so `target` doesn't match `data-target`
+    let name = regex::escape(name);
+    let re = Regex::new(&format!(r#"(?:^|\s){name}="([^"]*)""#)).unwrap();
+    let value = re.captures(attrs)?.get(1)?.as_str();
+    let value = value
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_match_the_whole_name() {
+        let attrs = r#" data-target="wrong" data-action="nope" target="dog_1" action="replace""#;
+        assert_eq!(attr(attrs, "target").as_deref(), Some("dog_1"));
+        assert_eq!(attr(attrs, "action").as_deref(), Some("replace"));
+        assert_eq!(attr(" data-targets=\".dogs\"", "targets"), None);
+        assert_eq!(
+            attr("targets=\"a &amp; b\"", "targets").as_deref(),
+            Some("a & b")
+        );
+    }
+}
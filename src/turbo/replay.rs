@@ -0,0 +1,333 @@
+use super::{ChannelPattern, TurboMessage, TurboPayload};
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::future::{ready, Ready};
+use std::hash::{BuildHasher, RandomState};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How many messages, across every channel, are kept around for clients that reconnect
+pub(crate) const DEFAULT_REPLAY_CAPACITY: usize = 1000;
+/// How long messages are kept around for clients that reconnect
+pub(crate) const DEFAULT_REPLAY_TTL: Duration = Duration::from_secs(60 * 5);
+
+/// Remembers recent messages so reconnecting clients can catch up.
+///
+/// Every message gets the next id of a single sequence shared by all channels,
+/// so a client only needs to remember the last id it saw.
+/// Each sequence has its own epoch, so ids from before a restart are never mistaken for new ones.
+#[derive(Debug)]
+pub(crate) struct ReplayLog {
+    capacity: usize,
+    ttl: Duration,
+    epoch: u64,
+    /// oldest first, so expired messages are always at the front
+    messages: VecDeque<Logged>,
+    last_id: u64,
+    /// Messages up to this id are no longer buffered
+    floor: u64,
+}
+
+#[derive(Debug)]
+struct Logged {
+    at: Instant,
+    msg: Arc<TurboMessage>,
+}
+
+/// The messages a reconnecting client missed
+pub(crate) struct Replay {
+    pub messages: Vec<Arc<TurboMessage>>,
+    /// true if some of the missed messages are no longer in the buffer
+    pub gap: bool,
+}
+
+impl Default for ReplayLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_CAPACITY, DEFAULT_REPLAY_TTL)
+    }
+}
+
+impl ReplayLog {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            epoch: new_epoch(),
+            messages: VecDeque::default(),
+            last_id: 0,
+            floor: 0,
+        }
+    }
+
+    pub fn configure(&mut self, capacity: usize, ttl: Duration) {
+        self.capacity = capacity;
+        self.ttl = ttl;
+        self.expire();
+    }
+
+    /// Keeps the message for replay, giving it the next id if it doesn't have one
+    pub fn record(&mut self, payload: TurboPayload) -> Arc<TurboMessage> {
+        let (epoch, id) = match payload.id {
+            0 => (self.epoch, self.last_id + 1),
+            id => (payload.epoch, id),
+        };
+        if epoch != self.epoch || id != self.last_id + 1 {
+            // messages were missed, or whoever assigns the ids restarted.
+            // Either way what is buffered can't be trusted to be complete
+            self.messages.clear();
+            self.epoch = epoch;
+            self.floor = id - 1;
+        }
+        self.last_id = id;
+        let msg = Arc::new(TurboMessage {
+            id,
+            epoch,
+            channel: payload.channel,
+            html: payload.html,
+            origin: payload.origin,
+        });
+        self.messages.push_back(Logged {
+            at: Instant::now(),
+            msg: msg.clone(),
+        });
+        self.expire();
+        msg
+    }
+
+    /// The id of the latest message
+    pub fn cursor(&self) -> ReplayCursor {
+        ReplayCursor::new(self.epoch, self.last_id)
+    }
+
+    /// Every message on the matching channels sent after the cursor
+    pub fn since(&mut self, cursor: &ReplayCursor, patterns: &[ChannelPattern]) -> Replay {
+        self.expire();
+        let seen = cursor.id();
+        // a cursor from another epoch, or from the future, means the ids started over
+        let restarted = cursor.epoch() != self.epoch || seen > self.last_id;
+        let gap = restarted || seen < self.floor;
+        if gap {
+            return Replay {
+                messages: Vec::default(),
+                gap,
+            };
+        }
+        let messages = self
+            .messages
+            .iter()
+            .filter(|l| l.msg.id > seen)
+            .filter(|l| patterns.iter().any(|p| p.matches(&l.msg.channel)))
+            .map(|l| l.msg.clone())
+            .collect();
+        Replay { messages, gap }
+    }
+
+    /// Drops messages that are too old, or past the capacity
+    fn expire(&mut self) {
+        let now = Instant::now();
+        while let Some(logged) = self.messages.front() {
+            let full = self.messages.len() > self.capacity;
+            if !full && now.duration_since(logged.at) <= self.ttl {
+                break;
+            }
+            self.floor = logged.msg.id;
+            self.messages.pop_front();
+        }
+        if self.messages.is_empty() {
+            self.floor = self.last_id;
+        }
+    }
+}
+
+/// The id of the last message a client has seen, and the epoch it was given in.
+///
+/// Sent to the browser as the SSE event id, and sent back as `Last-Event-ID` when it reconnects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayCursor {
+    epoch: u64,
+    id: u64,
+}
+
+impl ReplayCursor {
+    pub fn new(epoch: u64, id: u64) -> Self {
+        Self { epoch, id }
+    }
+
+    /// The last id seen. 0 if nothing has been seen
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.id == 0
+    }
+}
+
+impl Display for ReplayCursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x}-{}", self.epoch, self.id)
+    }
+}
+
+impl FromStr for ReplayCursor {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (epoch, id) = s.trim().split_once('-').unwrap_or(("", s));
+        Ok(ReplayCursor {
+            epoch: u64::from_str_radix(epoch, 16)?,
+            id: id.parse()?,
+        })
+    }
+}
+
+/// A random, non-zero epoch for a new sequence of message ids
+pub(crate) fn new_epoch() -> u64 {
+    RandomState::new().hash_one(SystemTime::now()).max(1)
+}
+
+/// The `Last-Event-ID` header a browser sends when it reconnects an EventSource
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LastEventId(pub Option<String>);
+
+impl LastEventId {
+    pub fn as_deref(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+/// Allows you to request the LastEventId from an actix resource
+impl FromRequest for LastEventId {
+    type Error = actix_web::Error;
+    type Future = Ready<std::result::Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let id = req
+            .headers()
+            .get("Last-Event-ID")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+        ready(Ok(LastEventId(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(channel: &str) -> TurboPayload {
+        TurboPayload {
+            epoch: 0,
+            id: 0,
+            channel: channel.to_owned(),
+            html: String::new(),
+            origin: None,
+        }
+    }
+
+    fn ids(replay: &Replay) -> Vec<u64> {
+        replay.messages.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn messages_get_the_next_id_of_one_sequence() {
+        let mut log = ReplayLog::default();
+        assert!(log.cursor().is_empty());
+        assert_eq!(log.record(payload("dogs")).id, 1);
+        assert_eq!(log.record(payload("cats")).id, 2);
+        let msg = log.record(payload("dogs"));
+        assert_eq!(msg.id, 3);
+        assert_eq!(msg.epoch, log.cursor().epoch());
+        assert_eq!(log.cursor().id(), 3);
+    }
+
+    #[test]
+    fn since_returns_missed_messages_on_matching_channels() {
+        let mut log = ReplayLog::default();
+        let start = log.cursor();
+        for channel in ["dogs/1", "cats/1", "dogs/2"] {
+            log.record(payload(channel));
+        }
+        let patterns = [ChannelPattern::new("dogs/*")];
+        let replay = log.since(&start, &patterns);
+        assert!(!replay.gap);
+        assert_eq!(ids(&replay), vec![1, 3]);
+        let replay = log.since(&ReplayCursor::new(start.epoch(), 1), &patterns);
+        assert_eq!(ids(&replay), vec![3]);
+        let replay = log.since(&log.cursor(), &patterns);
+        assert!(!replay.gap);
+        assert!(replay.messages.is_empty());
+    }
+
+    #[test]
+    fn assigned_ids_are_kept_and_gaps_detected() {
+        let mut log = ReplayLog::default();
+        let assigned = |id| TurboPayload {
+            epoch: 7,
+            id,
+            ..payload("dogs")
+        };
+        assert_eq!(log.record(assigned(1)).id, 1);
+        assert_eq!(log.record(assigned(2)).id, 2);
+        let patterns = [ChannelPattern::new("dogs")];
+        // ids 3 and 4 never arrived, so a client that saw 2 can't be caught up
+        log.record(assigned(5));
+        assert!(log.since(&ReplayCursor::new(7, 2), &patterns).gap);
+        let replay = log.since(&ReplayCursor::new(7, 4), &patterns);
+        assert!(!replay.gap);
+        assert_eq!(ids(&replay), vec![5]);
+    }
+
+    #[test]
+    fn capacity_expires_the_oldest_messages() {
+        let mut log = ReplayLog::new(2, DEFAULT_REPLAY_TTL);
+        let start = log.cursor();
+        for _ in 0..3 {
+            log.record(payload("dogs"));
+        }
+        let patterns = [ChannelPattern::new("dogs")];
+        assert!(log.since(&start, &patterns).gap);
+        let replay = log.since(&ReplayCursor::new(start.epoch(), 1), &patterns);
+        assert!(!replay.gap);
+        assert_eq!(ids(&replay), vec![2, 3]);
+    }
+
+    #[test]
+    fn ttl_expires_old_messages() {
+        let mut log = ReplayLog::new(10, Duration::from_millis(20));
+        let start = log.cursor();
+        log.record(payload("dogs"));
+        std::thread::sleep(Duration::from_millis(40));
+        let patterns = [ChannelPattern::new("dogs")];
+        assert!(log.since(&start, &patterns).gap);
+        // a client that saw everything has nothing to miss
+        assert!(!log.since(&log.cursor(), &patterns).gap);
+    }
+
+    #[test]
+    fn cursors_from_the_future_or_another_epoch_are_gaps() {
+        let mut log = ReplayLog::default();
+        log.record(payload("dogs"));
+        let patterns = [ChannelPattern::new("dogs")];
+        let epoch = log.cursor().epoch();
+        assert!(log.since(&ReplayCursor::new(epoch, 5), &patterns).gap);
+        assert!(log.since(&ReplayCursor::new(epoch + 1, 0), &patterns).gap);
+        assert!(!log.since(&ReplayCursor::new(epoch, 0), &patterns).gap);
+    }
+
+    #[test]
+    fn cursors_round_trip_through_strings() {
+        let cursor = ReplayCursor::new(0xabc, 12);
+        assert_eq!(cursor.to_string(), "abc-12");
+        assert_eq!(" abc-12 ".parse::<ReplayCursor>(), Ok(cursor));
+        assert!("12".parse::<ReplayCursor>().is_err());
+        assert!("abc-".parse::<ReplayCursor>().is_err());
+    }
+}
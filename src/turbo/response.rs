@@ -0,0 +1,101 @@
+use super::{TurboStreamAction, TURBO_STREAM_MIME};
+use actix_web::body::BoxBody;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use yew::html::BaseComponent;
+
+/// A batch of TurboStreamActions sent to the browser as a single response.
+///
+/// After a form submit you usually need several updates at once.
+/// Collect them here and return the batch from your actix endpoint,
+/// or broadcast it with `TurboStream::broadcast_batch`
+///
+/// ```
+/// use gumbo_lib::turbo::{TurboStreamAction, TurboStreamResponse};
+///
+/// let batch = TurboStreamResponse::new()
+///     .push(TurboStreamAction::append("dogs").html("<li>Rex</li>"))
+///     .push(TurboStreamAction::update("dog_count").html("3"));
+/// assert_eq!(batch.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TurboStreamResponse {
+    actions: Vec<TurboStreamAction>,
+}
+
+impl TurboStreamResponse {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an action to the end of the batch
+    pub fn push(mut self, action: TurboStreamAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Render a Yew view into the action and add it to the end of the batch
+    pub async fn render<V, VM>(self, action: TurboStreamAction, args: VM) -> Self
+    where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        let action = action.render::<V, VM>(args).await;
+        self.push(action)
+    }
+
+    pub fn actions(&self) -> &[TurboStreamAction] {
+        &self.actions
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// All the actions' `<turbo-stream>` markup joined together
+    pub fn to_html(&self) -> String {
+        self.actions.iter().map(|a| a.to_html()).collect()
+    }
+
+    /// Build the response to send out from an Actix endpoint
+    pub fn finish<E>(self) -> Result<HttpResponse, E> {
+        Ok(HttpResponse::Ok()
+            .content_type(TURBO_STREAM_MIME)
+            .body(self.to_html()))
+    }
+}
+
+impl From<TurboStreamAction> for TurboStreamResponse {
+    fn from(action: TurboStreamAction) -> Self {
+        Self::new().push(action)
+    }
+}
+
+impl Extend<TurboStreamAction> for TurboStreamResponse {
+    fn extend<T: IntoIterator<Item = TurboStreamAction>>(&mut self, iter: T) {
+        self.actions.extend(iter);
+    }
+}
+
+impl FromIterator<TurboStreamAction> for TurboStreamResponse {
+    fn from_iter<T: IntoIterator<Item = TurboStreamAction>>(iter: T) -> Self {
+        Self {
+            actions: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Allows a TurboStreamResponse to be returned directly from an actix endpoint
+impl Responder for TurboStreamResponse {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
+        HttpResponse::Ok()
+            .content_type(TURBO_STREAM_MIME)
+            .body(self.to_html())
+    }
+}
@@ -0,0 +1,174 @@
+use super::{
+    verify_stream_name, ChannelPattern, LastEventId, TurboConnectionId, TurboSse, TurboStream,
+    SIGNED_STREAM_NAME_PARAM,
+};
+use actix_web::dev::{AppService, HttpServiceFactory};
+use actix_web::error::{ErrorForbidden, ErrorServiceUnavailable};
+use actix_web::web::{self, Data};
+use actix_web::{HttpRequest, HttpResponse};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The route parameter a plain channel name is read from
+const CHANNEL_PARAM: &str = "channel";
+
+/// A ready made endpoint that streams a channel to `<turbo-stream-source>` over SSE.
+///
+/// Mounted at `{path}/{channel}`, where channel is a signed stream name
+/// (see `TurboStreamSource`), or a plain channel name when `unsigned()` is used.
+/// Browsers that reconnect resume from their `Last-Event-ID`.
+///
+/// The TurboStream is read from the app_data.
+/// Streams are closed when `TurboStream::shutdown` is called.
+///
+/// ```
+/// use actix_web::{web, App};
+/// use gumbo_lib::turbo::{TurboSseService, TurboStream};
+///
+/// let turbo = TurboStream::default();
+/// let app = App::new()
+///     .app_data(web::Data::new(turbo.clone()))
+///     .service(TurboSseService::new("/streams"));
+/// ```
+///
+/// To close the streams when the server stops
+///
+/// ```no_run
+/// use actix_web::{web, App, HttpServer};
+/// use gumbo_lib::turbo::{TurboSseService, TurboStream};
+///
+/// #[actix_web::main]
+/// async fn main() -> std::io::Result<()> {
+///     let turbo = TurboStream::default();
+///     let data = turbo.clone();
+///     let server = HttpServer::new(move || {
+///         App::new()
+///             .app_data(web::Data::new(data.clone()))
+///             .service(TurboSseService::new("/streams"))
+///     })
+///     .disable_signals()
+///     .bind(("127.0.0.1", 8080))?
+///     .run();
+///     let handle = server.handle();
+///     actix_web::rt::spawn(async move {
+///         let _ = actix_web::rt::signal::ctrl_c().await;
+///         turbo.shutdown();
+///         handle.stop(true).await;
+///     });
+///     server.await
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TurboSseService {
+    path: String,
+    options: Arc<SseOptions>,
+}
+
+#[derive(Debug, Clone)]
+struct SseOptions {
+    signed: bool,
+    heartbeat: Option<Duration>,
+    retry: Option<Duration>,
+}
+
+impl TurboSseService {
+    /// Mount the service at the given path. The channel is read from the rest of the path
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            options: Arc::new(SseOptions {
+                signed: true,
+                heartbeat: Some(super::sse::DEFAULT_HEARTBEAT),
+                retry: None,
+            }),
+        }
+    }
+
+    /// Accept plain channel names instead of signed ones.
+    ///
+    /// Anyone can watch any channel, so only use this for public channels.
+    /// The name is matched exactly, a `*` in it is never treated as a glob
+    pub fn unsigned(mut self) -> Self {
+        Arc::make_mut(&mut self.options).signed = false;
+        self
+    }
+
+    /// How often to send a heartbeat comment while idle
+    pub fn heartbeat(mut self, every: Duration) -> Self {
+        Arc::make_mut(&mut self.options).heartbeat = Some(every);
+        self
+    }
+
+    /// Never send heartbeat comments
+    pub fn no_heartbeat(mut self) -> Self {
+        Arc::make_mut(&mut self.options).heartbeat = None;
+        self
+    }
+
+    /// Tell the browser how long to wait before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        Arc::make_mut(&mut self.options).retry = Some(retry);
+        self
+    }
+
+    /// The route pattern the service is mounted at
+    fn route(&self) -> String {
+        let param = match self.options.signed {
+            true => SIGNED_STREAM_NAME_PARAM,
+            false => CHANNEL_PARAM,
+        };
+        // channels can contain slashes, so take the rest of the path
+        format!("{}/{{{param}:.+}}", self.path.trim_end_matches('/'))
+    }
+}
+
+impl HttpServiceFactory for TurboSseService {
+    fn register(self, config: &mut AppService) {
+        let options = self.options.clone();
+        let handler = move |req: HttpRequest,
+                            turbo: Data<TurboStream>,
+                            last: LastEventId,
+                            connection: TurboConnectionId| {
+            let options = options.clone();
+            async move { stream(&req, &turbo, &options, last, &connection) }
+        };
+        web::resource(self.route())
+            .route(web::get().to(handler))
+            .register(config)
+    }
+}
+
+fn stream(
+    req: &HttpRequest,
+    turbo: &TurboStream,
+    options: &SseOptions,
+    last_event_id: LastEventId,
+    connection: &TurboConnectionId,
+) -> Result<HttpResponse, actix_web::Error> {
+    if turbo.is_shutdown() {
+        return Err(ErrorServiceUnavailable(""));
+    }
+    let channel = match options.signed {
+        true => req
+            .match_info()
+            .get(SIGNED_STREAM_NAME_PARAM)
+            .and_then(verify_stream_name),
+        false => req.match_info().get(CHANNEL_PARAM).map(|c| c.to_owned()),
+    };
+    let Some(channel) = channel else {
+        log::debug!("TurboSseService::invalid channel");
+        return Err(ErrorForbidden(""));
+    };
+    let stream = turbo
+        .watch(ChannelPattern::exact(channel))
+        .for_connection(connection);
+    let mut sse = TurboSse::new(stream).resume(last_event_id.as_deref());
+    sse = match options.heartbeat {
+        Some(every) => sse.heartbeat(every),
+        None => sse.no_heartbeat(),
+    };
+    if let Some(retry) = options.retry {
+        sse = sse.retry(retry);
+    }
+    Ok(sse.into_response())
+}
@@ -0,0 +1,234 @@
+use super::TURBO_CONNECTION_ID_PARAM;
+use super::{use_turbo_connection_id, ChannelPattern, TurboMessageStream, TurboStream};
+use crate::auth_secret::auth_key;
+use actix_web::dev::Payload;
+use actix_web::error::ErrorForbidden;
+use actix_web::{FromRequest, HttpRequest};
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use sha3::Sha3_256;
+use std::future::{ready, Ready};
+use yew::{function_component, html, Html, Properties};
+
+type HmacSha3 = Hmac<Sha3_256>;
+
+/// Keeps signatures for stream names from being valid anywhere else AUTH_SECRET is used
+const SIGNING_CONTEXT: &[u8] = b"gumbo turbo signed stream name";
+
+/// The route parameter `SignedStreamName` reads the signed name from
+pub const SIGNED_STREAM_NAME_PARAM: &str = "signed_stream_name";
+
+/// A key derived from the AUTH_SECRET, only used to sign stream names
+fn signing_key() -> Vec<u8> {
+    let mut mac = HmacSha3::new_from_slice(&auth_key()).expect("HMAC can take key of any size");
+    mac.update(SIGNING_CONTEXT);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn signature(encoded: &str) -> HmacSha3 {
+    let mut mac = HmacSha3::new_from_slice(&signing_key()).expect("HMAC can take key of any size");
+    mac.update(encoded.as_bytes());
+    mac
+}
+
+/// Signs a channel name so it can be handed to the browser.
+/// Only channels signed by this server will be accepted by `verify_stream_name`
+///
+/// Panics if the AUTH_SECRET is not set or is invalid.
+pub fn sign_stream_name(channel: &str) -> String {
+    let encoded = BASE64_URL_SAFE_NO_PAD.encode(channel);
+    let digest = signature(&encoded).finalize().into_bytes();
+    let hex = base16ct::lower::encode_string(&digest);
+    format!("{encoded}--{hex}")
+}
+
+/// Returns the channel name if the signed name was signed by this server
+///
+/// Panics if the AUTH_SECRET is not set or is invalid.
+pub fn verify_stream_name(signed: &str) -> Option<String> {
+    let (encoded, hex) = signed.rsplit_once("--")?;
+    let digest = base16ct::lower::decode_vec(hex).ok()?;
+    // verify_slice compares in constant time
+    signature(encoded).verify_slice(&digest).ok()?;
+    let channel = BASE64_URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    String::from_utf8(channel).ok()
+}
+
+/// A channel name from the `{signed_stream_name}` route parameter
+/// that has been verified as signed by this server.
+///
+/// Requests with a missing or forged name are rejected with a 403 Forbidden
+///
+/// ```
+/// use actix_web::web::Data;
+/// use actix_web::HttpResponse;
+/// use gumbo_lib::turbo::{SignedStreamName, TurboSse, TurboStream};
+///
+/// // route: /streams/{signed_stream_name}
+/// pub(crate) async fn stream(turbo: Data<TurboStream>, name: SignedStreamName) -> HttpResponse {
+///     TurboSse::new(name.watch(&turbo)).into_response()
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedStreamName(String);
+
+impl SignedStreamName {
+    /// The verified channel name
+    pub fn channel(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    /// Watch the verified channel
+    pub fn watch(&self, turbo: &TurboStream) -> TurboMessageStream {
+        turbo.watch(ChannelPattern::exact(self.0.as_str()))
+    }
+}
+
+/// Allows you to request a SignedStreamName from an actix resource
+impl FromRequest for SignedStreamName {
+    type Error = actix_web::Error;
+    type Future = Ready<std::result::Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let channel = req
+            .match_info()
+            .get(SIGNED_STREAM_NAME_PARAM)
+            .and_then(verify_stream_name);
+        match channel {
+            Some(channel) => ready(Ok(SignedStreamName(channel))),
+            None => {
+                log::debug!("SignedStreamName::invalid signature");
+                ready(Err(ErrorForbidden("")))
+            }
+        }
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TurboStreamSourceProps {
+    /// The channel to listen to. It is signed before being sent to the browser
+    pub channel: String,
+    /// The path of your SSE endpoint, the signed name is added to the end of it
+    pub src: String,
+}
+
+/// Connects the page to a TurboStream over SSE.
+///
+/// Renders `<turbo-stream-source>` with a signed stream name so visitors can't
+/// swap in a channel of their own.
+/// Inside a `TurboConnection` the page's id is sent as the `connection_id` query parameter
+#[function_component(TurboStreamSource)]
+pub fn turbo_stream_source(props: &TurboStreamSourceProps) -> Html {
+    let signed = sign_stream_name(&props.channel);
+    let mut src = crate::view::app_path2(props.src.trim_end_matches('/'), signed);
+    if let Some(id) = use_turbo_connection_id().as_ref().and_then(|c| c.id()) {
+        src = format!("{src}?{TURBO_CONNECTION_ID_PARAM}={id}");
+    }
+    html! {
+        <turbo-stream-source src={ src } />
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct TurboCableStreamSourceProps {
+    /// The channel to listen to. It is signed before being sent to the browser
+    pub channel: String,
+}
+
+/// Connects the page to a TurboStream over a WebSocket.
+///
+/// Renders `<turbo-cable-stream-source>` with a signed stream name,
+/// as expected by the `turbo_websocket` endpoint.
+/// Inside a `TurboConnection` the page's id is sent with the subscription
+#[function_component(TurboCableStreamSource)]
+pub fn turbo_cable_stream_source(props: &TurboCableStreamSourceProps) -> Html {
+    let signed = sign_stream_name(&props.channel);
+    let connection = use_turbo_connection_id().and_then(|c| c.id().map(|id| id.to_owned()));
+    // turbo-rails sends data attributes as subscription params, `data-connection-id` as `connection_id`
+    html! {
+        <turbo-cable-stream-source
+            channel="Turbo::StreamsChannel"
+            signed-stream-name={ signed }
+            data-connection-id={ connection } />
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_secret::set_test_auth_secret;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn signed_names_round_trip() {
+        set_test_auth_secret();
+        for channel in ["dogs", "dogs/12", "", "ünïcödé--with--dashes"] {
+            let signed = sign_stream_name(channel);
+            assert_eq!(verify_stream_name(&signed).as_deref(), Some(channel));
+        }
+    }
+
+    #[test]
+    fn tampered_channels_are_rejected() {
+        set_test_auth_secret();
+        let signed = sign_stream_name("dogs/12");
+        let (_, hex) = signed.rsplit_once("--").unwrap();
+        let forged = format!("{}--{hex}", BASE64_URL_SAFE_NO_PAD.encode("dogs/13"));
+        assert_eq!(verify_stream_name(&forged), None);
+    }
+
+    #[test]
+    fn tampered_signatures_are_rejected() {
+        set_test_auth_secret();
+        let signed = sign_stream_name("dogs/12");
+        let flipped = if signed.ends_with('0') { '1' } else { '0' };
+        let forged = format!("{}{flipped}", &signed[..signed.len() - 1]);
+        assert_eq!(verify_stream_name(&forged), None);
+        let (encoded, _) = signed.rsplit_once("--").unwrap();
+        assert_eq!(verify_stream_name(&format!("{encoded}--")), None);
+    }
+
+    #[test]
+    fn malformed_names_are_rejected() {
+        set_test_auth_secret();
+        let signed = sign_stream_name("dogs/12");
+        assert_eq!(verify_stream_name(&signed.replacen("--", "", 1)), None);
+        assert_eq!(verify_stream_name("dogs"), None);
+        let (encoded, hex) = signed.rsplit_once("--").unwrap();
+        let not_hex = format!("{encoded}--{}", "z".repeat(hex.len()));
+        assert_eq!(verify_stream_name(&not_hex), None);
+        let upper = format!("{encoded}--{}", hex.to_uppercase());
+        assert_eq!(verify_stream_name(&upper), None);
+    }
+
+    #[actix_web::test]
+    async fn the_extractor_forbids_forged_names() {
+        set_test_auth_secret();
+        let signed = sign_stream_name("dogs/12");
+        let req = TestRequest::default()
+            .param(SIGNED_STREAM_NAME_PARAM, signed)
+            .to_http_request();
+        let name = SignedStreamName::extract(&req).await.unwrap();
+        assert_eq!(name.channel(), "dogs/12");
+
+        let forged = format!(
+            "{}--{}",
+            BASE64_URL_SAFE_NO_PAD.encode("dogs/13"),
+            "0".repeat(64)
+        );
+        let req = TestRequest::default()
+            .param(SIGNED_STREAM_NAME_PARAM, forged)
+            .to_http_request();
+        let err = SignedStreamName::extract(&req).await.unwrap_err();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+
+        let missing = TestRequest::default().to_http_request();
+        let err = SignedStreamName::extract(&missing).await.unwrap_err();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+    }
+}
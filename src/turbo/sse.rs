@@ -0,0 +1,361 @@
+use super::{TurboConnectionId, TurboMessageStream};
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use futures::stream::{unfold, Stream};
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+
+/// How often a comment is sent down an idle SSE connection to keep proxies from closing it
+pub(crate) const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// A single Server-Sent Event, encoded following the EventSource spec.
+///
+/// Multi-line data is split across multiple `data:` lines,
+/// so HTML inside `<pre>` and `<textarea>` tags arrives untouched.
+///
+/// ```
+/// use gumbo_lib::turbo::SseEvent;
+///
+/// let event = SseEvent::new("<pre>a\nb</pre>").id("7").event("message");
+/// assert_eq!(
+///     event.encode(),
+///     "id: 7\nevent: message\ndata: <pre>a\ndata: b</pre>\n\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    id: Option<String>,
+    event: Option<String>,
+    retry: Option<Duration>,
+    data: Option<String>,
+    comment: Option<String>,
+}
+
+impl SseEvent {
+    /// An event carrying the given data
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            data: Some(data.into()),
+            ..Default::default()
+        }
+    }
+
+    /// A comment line. Ignored by the browser, useful as a heartbeat
+    pub fn comment(text: impl Into<String>) -> Self {
+        Self {
+            comment: Some(text.into()),
+            ..Default::default()
+        }
+    }
+
+    /// An event that only tells the browser how long to wait before reconnecting
+    pub fn retry_only(retry: Duration) -> Self {
+        Self {
+            retry: Some(retry),
+            ..Default::default()
+        }
+    }
+
+    /// The event id. The browser sends this back as `Last-Event-ID` when it reconnects
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// The event name. Browsers deliver unnamed events as `message`
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// How long the browser should wait before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// The wire format of this event
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        if let Some(comment) = &self.comment {
+            for line in split_lines(comment) {
+                out.push_str(": ");
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        // ids and event names can't span lines, so newlines are dropped
+        if let Some(id) = &self.id {
+            out.push_str("id: ");
+            out.push_str(&single_line(id));
+            out.push('\n');
+        }
+        if let Some(event) = &self.event {
+            out.push_str("event: ");
+            out.push_str(&single_line(event));
+            out.push('\n');
+        }
+        if let Some(retry) = &self.retry {
+            out.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        if let Some(data) = &self.data {
+            for line in split_lines(data) {
+                out.push_str("data: ");
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+        out
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::from(self.encode())
+    }
+}
+
+/// Splits on every kind of line ending the EventSource spec allows
+fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\r\n").flat_map(|l| l.split(['\r', '\n']))
+}
+
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], "")
+}
+
+/// Turns a TurboMessageStream into a ready to send SSE body.
+///
+/// Heartbeat comments are sent while the stream is idle so proxies don't close the connection.
+/// Every message carries an event id so a reconnecting browser can resume with `Last-Event-ID`.
+///
+/// ```
+/// use gumbo_lib::turbo::{LastEventId, TurboSse, TurboStream};
+/// use actix_web::web::Data;
+/// use actix_web::HttpResponse;
+/// use std::time::Duration;
+///
+/// // actix endpoint
+/// pub(crate) async fn stream(turbo: Data<TurboStream>, last: LastEventId) -> Result<HttpResponse, ()> {
+///   let sub = turbo.watch("dogs/create").resume(last.as_deref());
+///   Ok(TurboSse::new(sub).heartbeat(Duration::from_secs(30)).into_response())
+/// }
+/// ```
+pub struct TurboSse {
+    stream: TurboMessageStream,
+    heartbeat: Option<Duration>,
+    retry: Option<Duration>,
+    event: Option<String>,
+    guard: Option<(Duration, SseGuard)>,
+    close_at: Option<Instant>,
+}
+
+/// Checked periodically while a TurboSse is open. The stream is closed once it returns false
+pub type SseGuard = Box<dyn FnMut() -> bool + Send>;
+
+impl TurboSse {
+    pub fn new(stream: TurboMessageStream) -> Self {
+        Self {
+            stream,
+            heartbeat: Some(DEFAULT_HEARTBEAT),
+            retry: None,
+            event: None,
+            guard: None,
+            close_at: None,
+        }
+    }
+
+    /// Catch up on messages the browser missed while it was disconnected.
+    /// See `TurboMessageStream::resume`
+    pub fn resume(mut self, last_event_id: Option<&str>) -> Self {
+        self.stream = self.stream.resume(last_event_id);
+        self
+    }
+
+    /// Skip messages sent by this connection, see `TurboMessageStream::for_connection`
+    pub fn for_connection(mut self, connection: &TurboConnectionId) -> Self {
+        self.stream.set_connection(connection);
+        self
+    }
+
+    /// How often to send a heartbeat comment while idle.
+    /// A zero duration is the same as `no_heartbeat`
+    pub fn heartbeat(mut self, every: Duration) -> Self {
+        self.heartbeat = Some(every).filter(|every| !every.is_zero());
+        self
+    }
+
+    /// Never send heartbeat comments
+    pub fn no_heartbeat(mut self) -> Self {
+        self.heartbeat = None;
+        self
+    }
+
+    /// Tell the browser how long to wait before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Send every message as a named event.
+    /// NOTE: `<turbo-stream-source>` only listens for unnamed (`message`) events
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Re-check the guard every so often, closing the stream once it returns false.
+    /// Useful to stop sending to a user whose access has been revoked.
+    ///
+    /// A zero duration checks the guard before every message instead of on a timer
+    pub fn guard(mut self, every: Duration, guard: impl FnMut() -> bool + Send + 'static) -> Self {
+        self.guard = Some((every, Box::new(guard)));
+        self
+    }
+
+    /// Close the stream at the given time, for example when the session expires
+    pub fn close_at(mut self, at: Instant) -> Self {
+        self.close_at = Some(at);
+        self
+    }
+
+    /// The SSE body as a stream of bytes
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes, actix_web::Error>> + 'static {
+        let heartbeat = self.heartbeat.map(delayed_interval);
+        let guard = self.guard.map(|(every, guard)| {
+            let interval = Some(every).filter(|every| !every.is_zero());
+            (interval.map(delayed_interval), guard)
+        });
+        let state = SseState {
+            stream: self.stream,
+            heartbeat,
+            retry: self.retry,
+            event: self.event,
+            guard,
+            close_at: self.close_at,
+        };
+        unfold(state, |mut state| async move {
+            let event = state.next_event().await?;
+            let bytes = event.to_bytes();
+            state.stream.written(bytes.len());
+            Some((Ok(bytes), state))
+        })
+    }
+
+    /// A streaming `text/event-stream` response
+    pub fn into_response(self) -> HttpResponse {
+        HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header(("Cache-Control", "no-cache"))
+            .insert_header(("X-Accel-Buffering", "no"))
+            .streaming(self.into_stream())
+    }
+}
+
+/// An interval that doesn't fire right away
+fn delayed_interval(every: Duration) -> Interval {
+    let mut interval = interval_at(Instant::now() + every, every);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+/// Waits for the next tick, or forever if there is no interval
+async fn tick(interval: Option<&mut Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits until the deadline, or forever if there isn't one
+async fn deadline(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at).await,
+        None => std::future::pending().await,
+    }
+}
+
+struct SseState {
+    stream: TurboMessageStream,
+    heartbeat: Option<Interval>,
+    retry: Option<Duration>,
+    event: Option<String>,
+    /// Without an interval the guard is checked before every message
+    guard: Option<(Option<Interval>, SseGuard)>,
+    close_at: Option<Instant>,
+}
+
+impl SseState {
+    /// Waits for the next message or heartbeat. Returns None when the stream is closed
+    async fn next_event(&mut self) -> Option<SseEvent> {
+        if let Some(retry) = self.retry.take() {
+            return Some(SseEvent::retry_only(retry));
+        }
+        let msg = loop {
+            let guard_tick = self
+                .guard
+                .as_mut()
+                .and_then(|(interval, _)| interval.as_mut());
+            tokio::select! {
+                msg = self.stream.next() => break msg?,
+                _ = tick(self.heartbeat.as_mut()) => return Some(SseEvent::comment("heartbeat")),
+                _ = tick(guard_tick) => {
+                    if let Some((_, guard)) = &mut self.guard {
+                        if !guard() {
+                            log::debug!("TurboSse::guard failed, closing stream");
+                            return None;
+                        }
+                    }
+                }
+                _ = deadline(self.close_at) => return None,
+            }
+        };
+        if let Some((None, guard)) = &mut self.guard {
+            if !guard() {
+                log::debug!("TurboSse::guard failed, closing stream");
+                return None;
+            }
+        }
+        if let Some(heartbeat) = &mut self.heartbeat {
+            // no need for a heartbeat right after sending a message
+            heartbeat.reset();
+        }
+        let mut event = SseEvent::new(msg.html.as_str());
+        let cursor = self.stream.cursor();
+        if !cursor.is_empty() {
+            // lets the browser resume from here with Last-Event-ID
+            event = event.id(cursor.to_string());
+        }
+        if let Some(name) = &self.event {
+            event = event.event(name.as_str());
+        }
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::turbo::TurboStream;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn a_zero_guard_interval_checks_every_message() {
+        let turbo = TurboStream::default();
+        let mut allowed = 1;
+        let guard = move || {
+            allowed -= 1;
+            allowed >= 0
+        };
+        let sse = TurboSse::new(turbo.watch("dogs"))
+            .guard(Duration::ZERO, guard)
+            .into_stream();
+        let mut sse = std::pin::pin!(sse);
+        turbo.stream("dogs", "1");
+        turbo.stream("dogs", "2");
+        let first = sse.next().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&first).contains("data: 1"));
+        assert!(sse.next().await.is_none());
+    }
+}
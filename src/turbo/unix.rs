@@ -0,0 +1,333 @@
+use super::replay::new_epoch;
+use super::{TurboBackend, TurboFanout, TurboPayload};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+
+/// How long to wait before reconnecting to the broker
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// How many messages are queued up while the broker is unreachable
+const OUTBOX_CAPACITY: usize = 1024;
+/// The largest message that will be read from the socket
+const MAX_FRAME: usize = 16 * 1024 * 1024;
+
+/// Relays messages between every TurboStream connected to it over a Unix domain socket.
+///
+/// The broker gives every message its id, so all processes agree on them and a browser
+/// can resume with its `Last-Event-ID` no matter which process it reconnects to.
+///
+/// Run it in its own process, or spawn it from one of your workers,
+/// then give every TurboStream a `UnixSocketBackend` pointing at the same path.
+///
+/// ```no_run
+/// use gumbo_lib::turbo::{TurboBroker, TurboStream, UnixSocketBackend};
+///
+/// async fn setup() -> TurboStream {
+///     tokio::spawn(TurboBroker::new("/tmp/my_app_turbo.sock").run());
+///     TurboStream::default().with_backend(UnixSocketBackend::new("/tmp/my_app_turbo.sock"))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TurboBroker {
+    path: PathBuf,
+}
+
+impl TurboBroker {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Listen for TurboStreams and relay messages between them. Runs until the listener fails.
+    ///
+    /// Fails with `AddrInUse` if another broker is already listening on the path
+    pub async fn run(self) -> std::io::Result<()> {
+        self.remove_stale_socket().await?;
+        let listener = UnixListener::bind(&self.path)?;
+        let (tx, _rx) = broadcast::channel(OUTBOX_CAPACITY);
+        let sequencer = Arc::new(Sequencer {
+            tx,
+            epoch: new_epoch(),
+            last_id: Mutex::new(0),
+        });
+        let mut next_conn = 0;
+        loop {
+            let (conn, _addr) = listener.accept().await?;
+            next_conn += 1;
+            tokio::spawn(serve_connection(conn, next_conn, sequencer.clone()));
+        }
+    }
+
+    /// Cleans up the socket left behind by a broker that didn't shut down cleanly.
+    /// Fails with `AddrInUse` if another broker is still listening on it
+    async fn remove_stale_socket(&self) -> std::io::Result<()> {
+        use std::io::ErrorKind;
+        match UnixStream::connect(&self.path).await {
+            Ok(_) => {
+                let msg = format!(
+                    "a TurboBroker is already running on {}",
+                    self.path.display()
+                );
+                return Err(std::io::Error::new(ErrorKind::AddrInUse, msg));
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => (),
+            Err(err) => return Err(err),
+        }
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Gives every message passing through the broker the next id, and sends it to every connection.
+///
+/// Ids start over when the broker restarts, so they are sent with an epoch
+/// that lets streams tell the new ids apart from the old ones.
+#[derive(Debug)]
+struct Sequencer {
+    tx: broadcast::Sender<Vec<u8>>,
+    epoch: u64,
+    last_id: Mutex<u64>,
+}
+
+impl Sequencer {
+    fn send(&self, frame: &[u8]) {
+        let Some(mut payload) = TurboPayload::decode(frame) else {
+            log::warn!("TurboBroker: received an invalid message");
+            return;
+        };
+        // hold the lock while sending so every connection sees ids in order
+        let mut last_id = self.last_id.lock().unwrap();
+        *last_id += 1;
+        payload.epoch = self.epoch;
+        payload.id = *last_id;
+        let _ = self.tx.send(payload.encode());
+    }
+}
+
+/// Relays frames from one connection to every connection, including the one that sent it
+async fn serve_connection(conn: UnixStream, id: u64, sequencer: Arc<Sequencer>) {
+    let (mut reader, mut writer) = conn.into_split();
+    let mut rx = sequencer.tx.subscribe();
+    let read = async {
+        while let Ok(frame) = read_frame(&mut reader).await {
+            sequencer.send(&frame);
+        }
+    };
+    let write = async {
+        loop {
+            match rx.recv().await {
+                Ok(frame) => {
+                    if write_frame(&mut writer, &frame).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(count)) => {
+                    log::warn!("TurboBroker: connection {id} lagged, dropped {count} messages");
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    };
+    tokio::select! {
+        _ = read => (),
+        _ = write => (),
+    }
+    log::debug!("TurboBroker: connection {id} closed");
+}
+
+/// A TurboBackend that shares messages with other processes on this machine
+/// through a `TurboBroker` listening on a Unix domain socket.
+///
+/// Messages are sent to the broker, which gives them an id and relays them to every process,
+/// this one included. If the broker is unreachable messages are queued and sent once it is back,
+/// so nothing is delivered while it is down.
+///
+/// Must be given to a TurboStream from inside the tokio/actix runtime.
+#[derive(Debug)]
+pub struct UnixSocketBackend {
+    path: PathBuf,
+    outbox: OnceLock<mpsc::Sender<Vec<u8>>>,
+}
+
+impl UnixSocketBackend {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            outbox: OnceLock::new(),
+        }
+    }
+}
+
+impl TurboBackend for UnixSocketBackend {
+    fn start(&self, fanout: TurboFanout) {
+        let (tx, rx) = mpsc::channel(OUTBOX_CAPACITY);
+        if self.outbox.set(tx).is_err() {
+            log::warn!("TurboStream Error: UnixSocketBackend was started twice");
+            return;
+        }
+        tokio::spawn(connection_loop(self.path.clone(), rx, fanout));
+    }
+
+    fn publish(&self, payload: TurboPayload) {
+        let Some(outbox) = self.outbox.get() else {
+            log::warn!("TurboStream Error: UnixSocketBackend was never started");
+            return;
+        };
+        // delivered here once the broker sends it back with an id
+        if let Err(err) = outbox.try_send(payload.encode()) {
+            log::warn!("TurboStream Error: unable to queue message for broker: {err}");
+        }
+    }
+}
+
+/// Keeps a connection to the broker open until the TurboStream is dropped
+async fn connection_loop(path: PathBuf, mut outbox: mpsc::Receiver<Vec<u8>>, fanout: TurboFanout) {
+    loop {
+        match UnixStream::connect(&path).await {
+            Ok(conn) => {
+                log::debug!("UnixSocketBackend: connected to {}", path.display());
+                if !relay(conn, &mut outbox, &fanout).await {
+                    return;
+                }
+                log::warn!("UnixSocketBackend: lost connection to {}", path.display());
+            }
+            Err(err) => log::warn!(
+                "UnixSocketBackend: unable to connect to {}: {err}",
+                path.display()
+            ),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Sends and receives frames until the connection drops.
+/// Returns false once the outbox is closed, meaning the TurboStream is gone
+async fn relay(
+    conn: UnixStream,
+    outbox: &mut mpsc::Receiver<Vec<u8>>,
+    fanout: &TurboFanout,
+) -> bool {
+    let (mut reader, mut writer) = conn.into_split();
+    let read = async {
+        while let Ok(frame) = read_frame(&mut reader).await {
+            match TurboPayload::decode(&frame) {
+                Some(payload) => {
+                    fanout.deliver(payload);
+                }
+                None => log::warn!("UnixSocketBackend: received an invalid message"),
+            }
+        }
+        true
+    };
+    let write = async {
+        while let Some(frame) = outbox.recv().await {
+            if write_frame(&mut writer, &frame).await.is_err() {
+                return true;
+            }
+        }
+        false
+    };
+    tokio::select! {
+        keep_going = read => keep_going,
+        keep_going = write => keep_going,
+    }
+}
+
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> std::io::Result<Vec<u8>> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME {
+        return Err(std::io::Error::other("Turbo frame too large"));
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+async fn write_frame(writer: &mut (impl AsyncWrite + Unpin), frame: &[u8]) -> std::io::Result<()> {
+    writer.write_u32(frame.len() as u32).await?;
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::turbo::TurboStream;
+    use tokio::sync::oneshot;
+
+    /// Runs a broker on its own runtime, so stopping it drops every connection too
+    fn start_broker(path: &Path) -> (oneshot::Sender<()>, std::thread::JoinHandle<()>) {
+        let (stop, stopped) = oneshot::channel();
+        let broker = TurboBroker::new(path);
+        let thread = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async {
+                tokio::select! {
+                    _ = broker.run() => (),
+                    _ = stopped => (),
+                }
+            });
+        });
+        (stop, thread)
+    }
+
+    async fn next_html(stream: &mut crate::turbo::TurboMessageStream) -> String {
+        let next = tokio::time::timeout(Duration::from_secs(5), stream.next());
+        next.await.unwrap().unwrap().html.clone()
+    }
+
+    #[tokio::test]
+    async fn a_running_broker_is_not_replaced() {
+        let path = std::env::temp_dir().join(format!("gumbo_taken_{}.sock", std::process::id()));
+        let (stop, thread) = start_broker(&path);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let err = TurboBroker::new(&path).run().await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+        assert!(UnixStream::connect(&path).await.is_ok());
+
+        stop.send(()).unwrap();
+        thread.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn streams_are_refreshed_when_the_broker_restarts() {
+        let path = std::env::temp_dir().join(format!("gumbo_broker_{}.sock", std::process::id()));
+        let (stop, thread) = start_broker(&path);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let turbo = TurboStream::default().with_backend(UnixSocketBackend::new(&path));
+        let mut stream = turbo.watch("dogs");
+        turbo.stream("dogs", "before");
+        assert_eq!(next_html(&mut stream).await, "before");
+        let before = *stream.cursor();
+
+        stop.send(()).unwrap();
+        thread.join().unwrap();
+        let (stop, thread) = start_broker(&path);
+        // let the backend notice the old broker is gone
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // the new broker starts its ids over, which used to look like messages already seen
+        turbo.stream("dogs", "after");
+        assert!(next_html(&mut stream).await.contains(r#"action="refresh""#));
+        assert_ne!(stream.cursor().epoch(), before.epoch());
+        turbo.stream("dogs", "later");
+        assert_eq!(next_html(&mut stream).await, "later");
+
+        stop.send(()).unwrap();
+        thread.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
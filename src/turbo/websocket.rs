@@ -0,0 +1,211 @@
+use super::{verify_stream_name, ChannelMatch, ChannelPattern, TurboMessageStream, TurboStream};
+use super::{TurboConnectionId, TURBO_CONNECTION_ID_PARAM};
+use actix_web::http::header::{HeaderValue, SEC_WEBSOCKET_PROTOCOL};
+use actix_web::web::{Data, Payload};
+use actix_web::{HttpRequest, HttpResponse};
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, Session};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The ActionCable sub-protocol spoken by `@hotwired/turbo-rails` and `@rails/actioncable`
+const PROTOCOL: &str = "actioncable-v1-json";
+
+/// How often the server pings the client. ActionCable clients reconnect if pings stop
+const PING_INTERVAL: Duration = Duration::from_secs(3);
+
+/// An actix endpoint that serves a TurboStream over a WebSocket.
+///
+/// Speaks the ActionCable protocol used by `<turbo-cable-stream-source>`,
+/// so a single socket can subscribe to and unsubscribe from many channels.
+/// A subscription's identifier is expected to carry a channel signed with `sign_stream_name`
+/// in `signed_stream_name`, the way `TurboCableStreamSource` renders it.
+/// When it also carries a `connection_id`, messages sent with `stream_except`
+/// by that connection are skipped.
+///
+/// ```
+/// use actix_web::{web, App};
+/// use gumbo_lib::turbo::{turbo_websocket, TurboStream};
+///
+/// let turbo = web::Data::new(TurboStream::default());
+/// let app = App::new()
+///     .app_data(turbo)
+///     .route("/cable", web::get().to(turbo_websocket));
+/// ```
+pub async fn turbo_websocket(
+    req: HttpRequest,
+    body: Payload,
+    turbo: Data<TurboStream>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (mut response, session, incoming) = actix_ws::handle(&req, body)?;
+    // ActionCable clients close the socket if the server doesn't agree on the protocol
+    if offers_protocol(&req) {
+        let protocol = HeaderValue::from_static(PROTOCOL);
+        response
+            .headers_mut()
+            .insert(SEC_WEBSOCKET_PROTOCOL, protocol);
+    }
+    let stream = turbo.watch_many(Vec::<ChannelPattern>::default());
+    let incoming = incoming.aggregate_continuations();
+    actix_web::rt::spawn(run_cable(session, incoming, stream));
+    Ok(response)
+}
+
+fn offers_protocol(req: &HttpRequest) -> bool {
+    req.headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|p| p.trim() == PROTOCOL)
+}
+
+/// The subscriptions on a single socket, by their ActionCable identifier
+#[derive(Default)]
+struct Cable {
+    subscriptions: HashMap<String, ChannelPattern>,
+}
+
+impl Cable {
+    /// Handles a command from the client. Returns the replies to send back
+    fn command(&mut self, text: &str, stream: &mut TurboMessageStream) -> Vec<Value> {
+        let Ok(command) = serde_json::from_str::<Value>(text) else {
+            log::debug!("turbo_websocket::invalid command");
+            return Vec::default();
+        };
+        let Some(identifier) = command["identifier"].as_str() else {
+            return Vec::default();
+        };
+        match command["command"].as_str() {
+            Some("subscribe") => match resolve_channel(identifier) {
+                Some((pattern, connection)) => {
+                    // every subscription on a socket comes from the same page
+                    if connection.id().is_some() {
+                        stream.set_connection(&connection);
+                    }
+                    stream.subscribe(pattern.clone());
+                    self.subscriptions.insert(identifier.to_owned(), pattern);
+                    vec![json!({"identifier": identifier, "type": "confirm_subscription"})]
+                }
+                None => vec![json!({"identifier": identifier, "type": "reject_subscription"})],
+            },
+            Some("unsubscribe") => {
+                if let Some(pattern) = self.subscriptions.remove(identifier) {
+                    // other subscriptions on this socket may still want the channel
+                    if !self.subscriptions.values().any(|p| p == &pattern) {
+                        stream.unsubscribe(pattern);
+                    }
+                }
+                Vec::default()
+            }
+            _ => Vec::default(),
+        }
+    }
+
+    /// The messages to send to the client, one for every subscription the message matches
+    fn deliver(&self, matched: &ChannelMatch) -> Vec<Value> {
+        let channel = matched.channel();
+        self.subscriptions
+            .iter()
+            .filter(|(_, p)| **p == matched.pattern || p.matches(channel))
+            .map(|(identifier, _)| json!({"identifier": identifier, "message": matched.message.html}))
+            .collect()
+    }
+}
+
+/// Reads the signed channel, and the connection if there is one,
+/// out of an ActionCable subscription identifier
+fn resolve_channel(identifier: &str) -> Option<(ChannelPattern, TurboConnectionId)> {
+    let identifier: Value = serde_json::from_str(identifier).ok()?;
+    let signed = identifier["signed_stream_name"].as_str()?;
+    let channel = verify_stream_name(signed)?;
+    let connection = identifier[TURBO_CONNECTION_ID_PARAM]
+        .as_str()
+        .map(TurboConnectionId::from_id)
+        .unwrap_or_default();
+    Some((ChannelPattern::exact(channel), connection))
+}
+
+async fn run_cable(
+    mut session: Session,
+    mut incoming: AggregatedMessageStream,
+    mut stream: TurboMessageStream,
+) {
+    let mut cable = Cable::default();
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    if send(&mut session, json!({"type": "welcome"}))
+        .await
+        .is_err()
+    {
+        return;
+    }
+    loop {
+        let replies = tokio::select! {
+            msg = incoming.recv() => match msg {
+                Some(Ok(AggregatedMessage::Text(text))) => cable.command(&text, &mut stream),
+                Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                Some(Ok(AggregatedMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            matched = stream.next_match() => match matched {
+                Some(matched) => cable.deliver(&matched),
+                None => {
+                    let disconnect = json!({"type": "disconnect", "reason": "server_restart", "reconnect": true});
+                    let _ = send(&mut session, disconnect).await;
+                    break;
+                }
+            },
+            _ = ping.tick() => vec![json!({"type": "ping", "message": now_sec()})],
+        };
+        for reply in replies {
+            if send(&mut session, reply).await.is_err() {
+                return;
+            }
+        }
+    }
+    let _ = session.close(None).await;
+}
+
+async fn send(session: &mut Session, value: Value) -> Result<(), actix_ws::Closed> {
+    session.text(value.to_string()).await
+}
+
+fn now_sec() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_secret::set_test_auth_secret;
+    use crate::turbo::sign_stream_name;
+
+    #[tokio::test]
+    async fn subscriptions_skip_messages_from_their_connection() {
+        set_test_auth_secret();
+        let turbo = TurboStream::default();
+        let mut stream = turbo.watch_many(Vec::<ChannelPattern>::default());
+        let mut cable = Cable::default();
+        let identifier = json!({
+            "channel": "Turbo::StreamsChannel",
+            "signed_stream_name": sign_stream_name("dogs"),
+            "connection_id": "page-1",
+        });
+        let subscribe = json!({"command": "subscribe", "identifier": identifier.to_string()});
+        let replies = cable.command(&subscribe.to_string(), &mut stream);
+        assert_eq!(replies[0]["type"], "confirm_subscription");
+
+        let page = TurboConnectionId::from_id("page-1");
+        turbo.stream_except("dogs", "from page-1", &page);
+        turbo.stream("dogs", "from the server");
+        let matched = stream.next_match().await.unwrap();
+        assert_eq!(matched.message.html, "from the server");
+    }
+}
@@ -1,6 +1,18 @@
-use actix_web::HttpResponse;
+use crate::frame::{extract_frame, FrameRoot, FrameRootProps, TurboFrameRequest};
+use crate::respond::TURBO_STREAM_MIME;
+use actix_web::cookie::Cookie;
+use actix_web::http::header::{
+    CacheControl, CacheDirective, TryIntoHeaderPair, ETAG, IF_NONE_MATCH,
+};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use futures::stream::{self, unfold, StreamExt};
 use regex::Regex;
+use sha3::{Digest, Sha3_256};
 use std::fmt::Display;
+use std::future::ready;
+use std::rc::Rc;
 use yew::html::BaseComponent;
 use yew::ServerRenderer;
 
@@ -24,6 +36,292 @@ pub fn app_path2(src: impl Into<String>, tail: impl Display) -> String {
     format!("{src}/{tail}")
 }
 
+/// Builds the response for a rendered Yew view.
+///
+/// Use it when a page needs something other than a plain 200 OK,
+/// like a 422 for an invalid form or a 404 page.
+///
+/// ```
+/// use actix_web::cookie::Cookie;
+/// use actix_web::http::header::CacheDirective;
+/// use actix_web::http::StatusCode;
+/// use gumbo_lib::view::Render;
+/// # #[yew::function_component(NotFound)]
+/// # fn not_found() -> yew::Html { yew::html! {} }
+///
+/// async fn missing() -> actix_web::Result<actix_web::HttpResponse> {
+///     Render::page()
+///         .status(StatusCode::NOT_FOUND)
+///         .header(("X-Robots-Tag", "noindex"))
+///         .cookie(Cookie::new("seen_404", "1"))
+///         .cache([CacheDirective::NoStore])
+///         .view::<NotFound, _, _>(())
+///         .await
+/// }
+/// ```
+pub struct Render {
+    builder: HttpResponseBuilder,
+    status: StatusCode,
+    doctype: bool,
+    strip_comments: bool,
+    etag: Option<Conditional>,
+}
+
+/// What is needed to answer a conditional GET
+struct Conditional {
+    /// The `If-None-Match` header the browser sent
+    if_none_match: Option<String>,
+    /// An ETag made from a cache key, known before rendering
+    precomputed: Option<String>,
+}
+
+impl Render {
+    /// A full HTML page, starting with the doctype
+    pub fn page() -> Self {
+        let mut builder = HttpResponse::Ok();
+        builder.content_type("text/html; charset=utf-8");
+        Self {
+            builder,
+            status: StatusCode::OK,
+            doctype: true,
+            strip_comments: false,
+            etag: None,
+        }
+    }
+
+    /// A Turbo Stream message. HTML comments are stripped
+    pub fn turbo_stream() -> Self {
+        let mut builder = HttpResponse::Ok();
+        builder.content_type(TURBO_STREAM_MIME);
+        Self {
+            builder,
+            status: StatusCode::OK,
+            doctype: false,
+            strip_comments: true,
+            etag: None,
+        }
+    }
+
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.builder.status(status);
+        self.status = status;
+        self
+    }
+
+    /// Respond with a 422 Unprocessable Entity. Used when a form is not valid
+    pub fn invalid(self) -> Self {
+        self.status(StatusCode::UNPROCESSABLE_ENTITY)
+    }
+
+    /// Set a header, replacing any value it already had
+    pub fn header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        self.builder.insert_header(header);
+        self
+    }
+
+    /// Add a header, keeping any value it already had
+    pub fn append_header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        self.builder.append_header(header);
+        self
+    }
+
+    pub fn cookie(mut self, cookie: Cookie<'_>) -> Self {
+        self.builder.cookie(cookie);
+        self
+    }
+
+    /// Set the `Cache-Control` header
+    pub fn cache(self, directives: impl IntoIterator<Item = CacheDirective>) -> Self {
+        let directives = directives.into_iter().collect();
+        self.header(CacheControl(directives))
+    }
+
+    /// Send a strong ETag made from the rendered HTML.
+    /// When it matches the request's `If-None-Match` a 304 Not Modified is sent instead of the page
+    pub fn etag(mut self, req: &HttpRequest) -> Self {
+        self.etag = Some(Conditional {
+            if_none_match: if_none_match(req),
+            precomputed: None,
+        });
+        self
+    }
+
+    /// Send an ETag made from a cache key, for example a record's id and updated_at.
+    ///
+    /// The key must change whenever the page would. When it matches the request's
+    /// `If-None-Match` the view is never rendered
+    ///
+    /// ```
+    /// use actix_web::{HttpRequest, HttpResponse};
+    /// use gumbo_lib::view::Render;
+    /// # #[yew::function_component(DogShow)]
+    /// # fn dog_show() -> yew::Html { yew::html! {} }
+    ///
+    /// async fn show(req: HttpRequest) -> actix_web::Result<HttpResponse> {
+    ///     let (id, updated_at) = (12, "2024-01-01T00:00:00");
+    ///     Render::page()
+    ///         .etag_key(&req, format!("dog/{id}/{updated_at}"))
+    ///         .view::<DogShow, _, _>(())
+    ///         .await
+    /// }
+    /// ```
+    pub fn etag_key(mut self, req: &HttpRequest, key: impl AsRef<[u8]>) -> Self {
+        self.etag = Some(Conditional {
+            if_none_match: if_none_match(req),
+            precomputed: Some(etag_for(b"key:", key.as_ref())),
+        });
+        self
+    }
+
+    /// Strip out HTML comments
+    pub fn strip_comments(mut self) -> Self {
+        self.strip_comments = true;
+        self
+    }
+
+    /// Leave HTML comments in place
+    pub fn keep_comments(mut self) -> Self {
+        self.strip_comments = false;
+        self
+    }
+
+    /// Leave off the doctype, for pages that are only a fragment of HTML
+    pub fn no_doctype(mut self) -> Self {
+        self.doctype = false;
+        self
+    }
+
+    /// Render a Yew view to send out in an Actix Response
+    pub async fn view<V, VM, E>(self, args: VM) -> Result<HttpResponse, E>
+    where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        if let Some(etag) = self.precomputed_etag() {
+            if self.is_fresh(&etag) {
+                return Ok(self.not_modified(etag));
+            }
+        }
+        let renderer = ServerRenderer::<V>::with_props(|| args);
+        let html = renderer.render().await;
+        Ok(self.html(html))
+    }
+
+    /// Render a Yew view, sending each chunk out as soon as Yew has it.
+    ///
+    /// Pages that wait on suspense data start arriving in the browser
+    /// before the slow parts are done.
+    pub fn view_streaming<V, VM>(mut self, args: VM) -> HttpResponse
+    where
+        V: BaseComponent,
+        V: BaseComponent<Properties = VM>,
+        VM: Send + 'static,
+    {
+        // the body isn't known up front, so only a cache key can be used for the ETag
+        if let Some(etag) = self.precomputed_etag() {
+            if self.is_fresh(&etag) {
+                return self.not_modified(etag);
+            }
+            self.builder.insert_header((ETAG, etag));
+        }
+        let chunks = ServerRenderer::<V>::with_props(|| args).render_stream();
+        let stripper = self.strip_comments.then(CommentStripper::default);
+        let state = Some((Box::pin(chunks), stripper));
+        let body = unfold(state, |state| async move {
+            let (mut chunks, mut stripper) = state?;
+            match chunks.next().await {
+                Some(chunk) => {
+                    let chunk = match &mut stripper {
+                        Some(stripper) => stripper.push(&chunk),
+                        None => chunk,
+                    };
+                    Some((chunk, Some((chunks, stripper))))
+                }
+                None => Some((stripper?.finish(), None)),
+            }
+        });
+        // add the doctype markup. Yew doesn't like to render this.
+        let doctype = self.doctype.then(|| "<!DOCTYPE html>\n".to_owned());
+        let body = stream::iter(doctype)
+            .chain(body)
+            .filter(|chunk| ready(!chunk.is_empty()))
+            .map(|chunk| Ok::<_, actix_web::Error>(Bytes::from(chunk)));
+        self.builder.streaming(body)
+    }
+
+    /// Send already rendered HTML out in an Actix Response
+    pub fn html(mut self, html: impl Into<String>) -> HttpResponse {
+        let mut html: String = html.into();
+        if self.strip_comments {
+            html = strip_html_comments(&html);
+        }
+        if self.doctype {
+            // add the doctype markup. Yew doesn't like to render this.
+            html = format!("<!DOCTYPE html>\n{html}");
+        }
+        if self.etag.is_some() {
+            let etag = self
+                .precomputed_etag()
+                .unwrap_or_else(|| etag_for(b"html:", html.as_bytes()));
+            if self.is_fresh(&etag) {
+                return self.not_modified(etag);
+            }
+            self.builder.insert_header((ETAG, etag));
+        }
+        self.builder.body(html)
+    }
+
+    fn precomputed_etag(&self) -> Option<String> {
+        self.etag.as_ref()?.precomputed.clone()
+    }
+
+    /// True if the browser already has the page with this ETag.
+    /// Only successful responses are ever "not modified"
+    fn is_fresh(&self, etag: &str) -> bool {
+        let Some(if_none_match) = self.etag.as_ref().and_then(|c| c.if_none_match.as_ref()) else {
+            return false;
+        };
+        if !self.status.is_success() {
+            return false;
+        }
+        // If-None-Match uses the weak comparison, so W/ is ignored
+        if_none_match
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+    }
+
+    fn not_modified(mut self, etag: String) -> HttpResponse {
+        self.builder
+            .status(StatusCode::NOT_MODIFIED)
+            .insert_header((ETAG, etag))
+            .finish()
+    }
+}
+
+fn if_none_match(req: &HttpRequest) -> Option<String> {
+    let values: Vec<&str> = req
+        .headers()
+        .get_all(IF_NONE_MATCH)
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    match values.is_empty() {
+        true => None,
+        false => Some(values.join(",")),
+    }
+}
+
+/// A strong ETag, the quoted SHA3 hash of the content
+fn etag_for(kind: &[u8], content: &[u8]) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(kind);
+    hasher.update(content);
+    let hash = hasher.finalize();
+    let hex_hash = base16ct::lower::encode_string(&hash[..16]);
+    format!("\"{hex_hash}\"")
+}
+
 /// Render a Yew view to send out in an Actix Response
 pub async fn render<V, VM, E>(args: VM) -> Result<HttpResponse, E>
 where
@@ -31,13 +329,29 @@ where
     V: BaseComponent<Properties = VM>,
     VM: Send + 'static,
 {
-    let renderer = ServerRenderer::<V>::with_props(|| args);
-    let html = renderer.render().await;
-    // add the doctype markup. Yew doesn't like to render this.
-    let html = format!("<!DOCTYPE html>\n{html}");
-    Ok(HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html))
+    Render::page().view::<V, VM, E>(args).await
+}
+
+/// Render a Yew view to send out in an Actix Response
+/// Chunks are sent as soon as they are rendered, instead of waiting for the whole page
+pub async fn render_streaming<V, VM, E>(args: VM) -> Result<HttpResponse, E>
+where
+    V: BaseComponent,
+    V: BaseComponent<Properties = VM>,
+    VM: Send + 'static,
+{
+    Ok(Render::page().view_streaming::<V, VM>(args))
+}
+
+/// Render a Yew view to send out in an Actix Response
+/// Sends an ETag, and a 304 Not Modified when the browser already has the page
+pub async fn render_etag<V, VM, E>(req: &HttpRequest, args: VM) -> Result<HttpResponse, E>
+where
+    V: BaseComponent,
+    V: BaseComponent<Properties = VM>,
+    VM: Send + 'static,
+{
+    Render::page().etag(req).view::<V, VM, E>(args).await
 }
 
 /// Render a Yew view to send out in an Actix Response
@@ -48,14 +362,7 @@ where
     V: BaseComponent<Properties = VM>,
     VM: Send + 'static,
 {
-    let renderer = ServerRenderer::<V>::with_props(|| args);
-    let html = renderer.render().await;
-    let html = strip_html_comments(&html);
-    // add the doctype markup. Yew doesn't like to render this.
-    let html = format!("<!DOCTYPE html>\n{html}");
-    Ok(HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(html))
+    Render::page().strip_comments().view::<V, VM, E>(args).await
 }
 
 /// Render a Yew view to send out in an Actix Response for a Turbo Stream
@@ -65,12 +372,7 @@ where
     V: BaseComponent<Properties = VM>,
     VM: Send + 'static,
 {
-    let renderer = ServerRenderer::<V>::with_props(|| args);
-    let html = renderer.render().await;
-    let html = strip_html_comments(&html);
-    Ok(HttpResponse::Ok()
-        .content_type("text/vnd.turbo-stream.html")
-        .body(html))
+    Render::turbo_stream().view::<V, VM, E>(args).await
 }
 
 /// Render a Yew view to send out in an Actix Response for a Turbo Stream
@@ -81,11 +383,77 @@ where
     V: BaseComponent<Properties = VM>,
     VM: Send + 'static,
 {
-    let renderer = ServerRenderer::<V>::with_props(|| args);
-    let html = renderer.render().await;
-    Ok(HttpResponse::Ok()
-        .content_type("text/vnd.turbo-stream.html")
-        .body(html))
+    Render::turbo_stream()
+        .keep_comments()
+        .view::<V, VM, E>(args)
+        .await
+}
+
+/// Render a Yew view to send out in an Actix Response
+/// When the request came from a `<turbo-frame>` inside the frame view F, only F is rendered.
+/// Otherwise the full page V is rendered, which usually includes F.
+///
+/// Only the props of the view being rendered are built.
+/// Views can call `frame::use_turbo_frame` to skip rendering parts of the page Turbo will discard.
+///
+/// ```
+/// use actix_web::HttpResponse;
+/// use gumbo_lib::frame::{TurboFrame, TurboFrameRequest};
+/// use gumbo_lib::view::render_frame;
+/// use yew::prelude::*;
+///
+/// #[function_component(DogFrame)]
+/// fn dog_frame() -> Html {
+///     html! { <TurboFrame id="dog_1">{"Fido"}</TurboFrame> }
+/// }
+///
+/// #[function_component(DogPage)]
+/// fn dog_page() -> Html {
+///     html! { <main><h1>{"Dogs"}</h1><DogFrame /></main> }
+/// }
+///
+/// async fn show(frame: TurboFrameRequest) -> actix_web::Result<HttpResponse> {
+///     render_frame::<DogPage, DogFrame, actix_web::Error>(&frame, || (), || ()).await
+/// }
+/// ```
+pub async fn render_frame<V, F, E>(
+    frame: &TurboFrameRequest,
+    page: impl FnOnce() -> V::Properties,
+    frame_view: impl FnOnce() -> F::Properties,
+) -> Result<HttpResponse, E>
+where
+    V: BaseComponent,
+    V::Properties: Send + 'static,
+    F: BaseComponent,
+    F::Properties: Send + 'static,
+{
+    let requested = frame.id().map(|id| id.to_owned());
+    if let Some(id) = frame.id() {
+        let html = render_in_frame::<F>(requested.clone(), frame_view()).await;
+        if let Some(frame_html) = extract_frame(&html, id) {
+            return Ok(Render::page()
+                .header(("Vary", "Turbo-Frame"))
+                .no_doctype()
+                .strip_comments()
+                .html(frame_html));
+        }
+    }
+    // not a frame request, or one from a frame F doesn't render
+    let html = render_in_frame::<V>(requested, page()).await;
+    Ok(Render::page().header(("Vary", "Turbo-Frame")).html(html))
+}
+
+/// Renders a view with `frame::use_turbo_frame` returning the requested frame
+async fn render_in_frame<V>(frame: Option<String>, props: V::Properties) -> String
+where
+    V: BaseComponent,
+    V::Properties: Send + 'static,
+{
+    let renderer = ServerRenderer::<FrameRoot<V>>::with_props(move || FrameRootProps {
+        frame,
+        props: Rc::new(props),
+    });
+    renderer.render().await
 }
 
 /// Render a Yew view to send out in an Actix Response
@@ -97,9 +465,208 @@ pub fn redirect<E>(path: impl Into<String>) -> Result<HttpResponse, E> {
         .finish())
 }
 
+/// Escapes a value the same way Yew does for double quoted attributes
+pub(crate) fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
 /// Simple little function to remove HTML comments for the YEW render
 fn strip_html_comments(input: &str) -> String {
     // (?s) enables "dot matches newline"
     let re = Regex::new(r"(?s)<!--.*?-->").unwrap();
     re.replace_all(input, "").into_owned()
 }
+
+const COMMENT_OPEN: &str = "<!--";
+const COMMENT_CLOSE: &str = "-->";
+
+/// Removes HTML comments from a stream of chunks, the same way `strip_html_comments` does.
+/// A comment can start in one chunk and end in another
+#[derive(Debug, Default)]
+struct CommentStripper {
+    in_comment: bool,
+    /// Held back until the next chunk: the end of the last chunk in case it is the start
+    /// of a comment, or the comment so far in case it is never closed
+    held: String,
+}
+
+impl CommentStripper {
+    /// Returns the part of the chunk that is safe to send
+    fn push(&mut self, chunk: &str) -> String {
+        let mut input = std::mem::take(&mut self.held);
+        input.push_str(chunk);
+        let mut rest = input.as_str();
+        let mut out = String::with_capacity(rest.len());
+        loop {
+            if self.in_comment {
+                // rest starts with the comment's COMMENT_OPEN
+                let body = &rest[COMMENT_OPEN.len()..];
+                match body.find(COMMENT_CLOSE) {
+                    Some(at) => {
+                        rest = &body[at + COMMENT_CLOSE.len()..];
+                        self.in_comment = false;
+                        continue;
+                    }
+                    None => {
+                        self.held = rest.to_owned();
+                        return out;
+                    }
+                }
+            }
+            if let Some(at) = rest.find(COMMENT_OPEN) {
+                out.push_str(&rest[..at]);
+                rest = &rest[at..];
+                self.in_comment = true;
+                continue;
+            }
+            // hold back anything that could be the start of a comment
+            let keep = (1..COMMENT_OPEN.len())
+                .rev()
+                .find(|len| rest.ends_with(&COMMENT_OPEN[..*len]))
+                .unwrap_or(0);
+            let (done, held) = rest.split_at(rest.len() - keep);
+            out.push_str(done);
+            self.held = held.to_owned();
+            return out;
+        }
+    }
+
+    /// Whatever was held back, now that there are no more chunks.
+    /// A comment that is never closed is kept, like `strip_html_comments` does
+    fn finish(self) -> String {
+        self.held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_frame, strip_html_comments, CommentStripper};
+    use crate::frame::{TurboFrame, TurboFrameRequest};
+    use actix_web::body::to_bytes;
+    use actix_web::test::TestRequest;
+    use actix_web::FromRequest;
+    use yew::prelude::*;
+
+    #[function_component(DogFrame)]
+    fn dog_frame() -> Html {
+        html! {
+            <TurboFrame id="dog_1">
+                { Html::from_html_unchecked("<!-- kept out of frames -->".into()) }
+                {"Fido"}
+            </TurboFrame>
+        }
+    }
+
+    #[function_component(DogPage)]
+    fn dog_page() -> Html {
+        html! {
+            <main>
+                { Html::from_html_unchecked("<!-- kept on full pages -->".into()) }
+                <DogFrame />
+            </main>
+        }
+    }
+
+    /// The Vary header and body rendered for a request from the frame
+    async fn frame_response(frame: Option<&str>) -> (String, String) {
+        let mut req = TestRequest::default();
+        if let Some(frame) = frame {
+            req = req.insert_header(("Turbo-Frame", frame));
+        }
+        let frame = TurboFrameRequest::extract(&req.to_http_request())
+            .await
+            .unwrap();
+        let page_rendered = frame.id() != Some("dog_1");
+        let page = move || {
+            assert!(
+                page_rendered,
+                "the full page shouldn't be rendered for its frame"
+            );
+        };
+        let res = render_frame::<DogPage, DogFrame, actix_web::Error>(&frame, page, || ())
+            .await
+            .unwrap();
+        let vary = res
+            .headers()
+            .get("Vary")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let body = to_bytes(res.into_body()).await.ok().unwrap();
+        (vary, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn frame_requests_only_render_the_frame() {
+        let (vary, body) = frame_response(Some("dog_1")).await;
+        assert_eq!(body, r#"<turbo-frame id="dog_1">Fido</turbo-frame>"#);
+        assert_eq!(vary, "Turbo-Frame");
+    }
+
+    #[actix_web::test]
+    async fn other_requests_render_the_full_page() {
+        for frame in [None, Some("cat_1")] {
+            let (vary, body) = frame_response(frame).await;
+            assert!(body.starts_with("<!DOCTYPE html>"));
+            assert!(body.contains("<main>"));
+            assert!(body.contains("kept on full pages"));
+            assert_eq!(vary, "Turbo-Frame");
+        }
+    }
+
+    fn stream(chunks: &[&str]) -> String {
+        let mut stripper = CommentStripper::default();
+        let mut out: String = chunks.iter().map(|c| stripper.push(c)).collect();
+        out.push_str(&stripper.finish());
+        out
+    }
+
+    const PAGES: &[&str] = &[
+        "<p>a</p><!-- one --><p>b</p>",
+        "<!--x--><!---->a<!-- - -- ->-->b",
+        "<p>a</p><!-- never closed <p>b</p>",
+        "a <!- not a comment -> b <! -- c",
+        "<!--> still open --> done",
+        "<p>é</p><!--ü-->ß",
+    ];
+
+    #[test]
+    fn matches_strip_html_comments_in_one_chunk() {
+        for page in PAGES {
+            assert_eq!(stream(&[page]), strip_html_comments(page), "{page}");
+        }
+    }
+
+    #[test]
+    fn comment_tags_split_at_every_offset() {
+        for page in PAGES {
+            let expected = strip_html_comments(page);
+            for (at, _) in page.char_indices().skip(1) {
+                let (a, b) = page.split_at(at);
+                assert_eq!(stream(&[a, b]), expected, "{a:?} | {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn comment_tags_split_across_many_chunks() {
+        for page in PAGES {
+            let expected = strip_html_comments(page);
+            let chars: Vec<String> = page.chars().map(String::from).collect();
+            let chunks: Vec<&str> = chars.iter().map(|c| c.as_str()).collect();
+            assert_eq!(stream(&chunks), expected, "{page}");
+        }
+    }
+
+    #[test]
+    fn unclosed_comment_is_kept() {
+        let page = "<p>a</p><!-- never closed";
+        assert_eq!(strip_html_comments(page), page);
+        assert_eq!(stream(&["<p>a</p><!", "-- never ", "closed"]), page);
+    }
+}